@@ -0,0 +1,225 @@
+use std::{alloc::{Allocator, Layout}, fs::{File, OpenOptions}, path::Path, ptr::NonNull};
+
+use memmap2::MmapMut;
+
+use crate::{compose::Owns, error::{AllocError, MappedError}};
+
+use super::Arena;
+
+const MAGIC: [u8; 8] = *b"NFARENA1";
+
+/// Stored at the start of the file, everything after it belongs to the arena.
+#[repr(C)]
+struct MappedHeader {
+    magic: [u8; 8],
+    /// size of the whole file when it was created.
+    size: u64,
+    /// bump offset from the start of the file.
+    offset: u64,
+    /// offset of the root object from the start of the file, 0 if there's none.
+    root: u64,
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<MappedHeader>();
+
+/// Arena that bump allocates inside a memory mapped file.
+/// # Concepts
+/// The bump offset is kept in a header at the start of the file, so reopening the file
+/// resumes where the last arena left off and everything allocated before is still there.
+/// The file can be mapped at a different address every time, so data structures in it
+/// should link to each other with [`super::RelPtr`] instead of regular pointers, and record
+/// their entry point with [`MappedFileArena::set_root`].
+/// ```no_run
+/// use std::{alloc::Layout, ptr::NonNull};
+/// use nightfall_allocators::arena::{Arena, MappedFileArena};
+///
+/// let arena = MappedFileArena::open_or_create("numbers.arena", 1024*1024).unwrap();
+/// if let Some(root) = arena.root::<u64>() {
+///     println!("found {}", unsafe { root.read() });
+/// } else {
+///     let value = arena.arena_alloc(Layout::new::<u64>()).unwrap().cast::<u64>();
+///     unsafe { value.write(42) };
+///     arena.set_root(Some(value));
+/// }
+/// ```
+/// The file's contents are trusted, opening a file that was modified by anything else is
+/// as unsafe as dereferencing its pointers.
+pub struct MappedFileArena {
+    map: MmapMut,
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl MappedFileArena {
+    /// Creates a new arena file of `size` bytes, overwriting any existing file.
+    pub fn create(path: impl AsRef<Path>, size: usize) -> Result<Self, MappedError> {
+        if size <= HEADER_SIZE {
+            Err(MappedError::InvalidFile("size too small to hold the header"))?
+        }
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        file.set_len(size as u64)?;
+        let arena = Self::map(&file)?;
+        unsafe { arena.ptr.cast::<MappedHeader>().write(MappedHeader { magic: MAGIC, size: size as u64, offset: HEADER_SIZE as u64, root: 0 }) };
+        Ok(arena)
+    }
+    /// Opens an existing arena file, resuming at its bump offset.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MappedError> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        if (file.metadata()?.len() as usize) < HEADER_SIZE {
+            Err(MappedError::InvalidFile("file too small to hold the header"))?
+        }
+        let arena = Self::map(&file)?;
+        let header = arena.header();
+        if header.magic != MAGIC {
+            Err(MappedError::InvalidFile("not an arena file"))?
+        }
+        if header.size as usize != arena.size || !(HEADER_SIZE as u64..=header.size).contains(&header.offset) || header.root >= header.size {
+            Err(MappedError::InvalidFile("corrupted header"))?
+        }
+        Ok(arena)
+    }
+    /// opens the file at `path` if it exists, or creates it with `size` bytes.
+    pub fn open_or_create(path: impl AsRef<Path>, size: usize) -> Result<Self, MappedError> {
+        if path.as_ref().exists() {
+            Self::open(path)
+        } else {
+            Self::create(path, size)
+        }
+    }
+    fn map(file: &File) -> Result<Self, MappedError> {
+        let mut map = unsafe { MmapMut::map_mut(file)? };
+        let ptr = map.as_mut_ptr();
+        let size = map.len();
+        Ok(Self { map, ptr, size })
+    }
+    /// writes every change back to the file.
+    pub fn flush(&self) -> Result<(), MappedError> {
+        Ok(self.map.flush()?)
+    }
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+    /// offset of `ptr` from the start of the file, if it's inside of it.
+    pub fn offset_of<T>(&self, ptr: NonNull<T>) -> Option<usize> {
+        let offset = (ptr.as_ptr() as usize).checked_sub(self.ptr as usize)?;
+        (offset < self.size).then_some(offset)
+    }
+    /// pointer to `offset` bytes from the start of the file.
+    pub fn at_offset<T>(&self, offset: usize) -> Option<NonNull<T>> {
+        (offset < self.size).then(|| NonNull::new(unsafe { self.ptr.add(offset) }).unwrap().cast())
+    }
+    /// Records the entry point of the data in the arena, so it can be found after reopening.
+    pub fn set_root<T>(&self, root: Option<NonNull<T>>) {
+        let offset = root.and_then(|root| self.offset_of(root)).unwrap_or(0);
+        self.header().root = offset as u64;
+    }
+    /// The entry point recorded with [`MappedFileArena::set_root`], it's up to the caller to use the same `T`.
+    pub fn root<T>(&self) -> Option<NonNull<T>> {
+        match self.header().root {
+            0 => None,
+            offset => self.at_offset(offset as usize),
+        }
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn header(&self) -> &mut MappedHeader {
+        unsafe { &mut *self.ptr.cast::<MappedHeader>() }
+    }
+}
+
+impl Arena for MappedFileArena {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        let header = self.header();
+        let current = header.offset as usize;
+        let available = self.size - current;
+        let address = self.ptr as usize + current;
+        let offset = current + (address.next_multiple_of(layout.align()) - address);
+        match offset.checked_add(layout.size()) {
+            Some(end) if end <= self.size => {
+                header.offset = end as u64;
+                // `offset` is the end of the mapping for zero sized allocations in a full arena
+                Ok(NonNull::slice_from_raw_parts(NonNull::new(unsafe { self.ptr.add(offset) }).unwrap(), layout.size()))
+            }
+            _ => Err(AllocError::OutOfMemory { requested: layout.size(), available }),
+        }
+    }
+    fn size(&self) -> usize {
+        self.size - HEADER_SIZE
+    }
+    fn allocated(&self) -> usize {
+        self.header().offset as usize - HEADER_SIZE
+    }
+    unsafe fn clear(&self) {
+        let header = self.header();
+        header.offset = HEADER_SIZE as u64;
+        header.root = 0;
+    }
+    fn is_clear(&self) -> bool {
+        self.header().offset as usize == HEADER_SIZE
+    }
+}
+
+unsafe impl Allocator for MappedFileArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        <Self as Arena>::arena_alloc(self, layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+        // empty deallocate function, since we clear Arenas, not deallocate.
+    }
+}
+
+impl Owns for MappedFileArena {
+    fn owns(&self, ptr: NonNull<u8>, _: Layout) -> bool {
+        self.offset_of(ptr).is_some_and(|offset| offset >= HEADER_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::Layout, ptr::NonNull};
+
+    use crate::{arena::{Arena, RelPtr}, error::MappedError};
+
+    use super::MappedFileArena;
+
+    struct Node {
+        value: u64,
+        next: RelPtr<Node>,
+    }
+    #[test]
+    fn persist_test() {
+        let path = std::env::temp_dir().join(format!("nightfall-mapped-{}.arena", std::process::id()));
+        {
+            let arena = MappedFileArena::create(&path, 4096).unwrap();
+            let mut previous: Option<NonNull<Node>> = None;
+            for value in 0..8 {
+                let node = arena.arena_alloc(Layout::new::<Node>()).unwrap().cast::<Node>();
+                unsafe {
+                    node.write(Node { value, next: RelPtr::null() });
+                    (*node.as_ptr()).next.set(previous);
+                }
+                previous = Some(node);
+            }
+            arena.set_root(previous);
+            arena.flush().unwrap();
+        }
+        let arena = MappedFileArena::open(&path).unwrap();
+        let allocated = arena.allocated();
+        assert!(allocated == 8*std::mem::size_of::<Node>(), "Testing the bump offset was restored");
+        let mut values = Vec::new();
+        let mut current = arena.root::<Node>().map(|root| unsafe { root.as_ref() });
+        while let Some(node) = current {
+            values.push(node.value);
+            current = unsafe { node.next.as_ref() };
+        }
+        assert!(values == [7, 6, 5, 4, 3, 2, 1, 0], "Testing the list survived being remapped: {values:?}");
+        arena.arena_alloc(Layout::new::<u64>()).unwrap();
+        assert!(arena.allocated() == allocated + 8, "Testing allocations resume after the old ones");
+        arena.arena_alloc(Layout::array::<u8>(arena.size() - arena.allocated()).unwrap()).unwrap();
+        assert!(arena.arena_alloc(Layout::new::<()>()).is_ok(), "Testing zero sized allocations in a full arena");
+        drop(arena);
+        std::fs::write(&path, [0u8; 64]).unwrap();
+        assert!(matches!(MappedFileArena::open(&path), Err(MappedError::InvalidFile(_))), "Testing foreign files are rejected");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
\ No newline at end of file
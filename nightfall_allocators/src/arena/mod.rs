@@ -2,6 +2,16 @@
 mod standard;
 mod ptr;
 mod sync;
+mod relptr;
+mod mapped;
+mod vec;
+#[cfg(target_os = "linux")]
+mod shm;
 pub use standard::*;
 pub use ptr::*;
-pub use sync::*;
\ No newline at end of file
+pub use sync::*;
+pub use relptr::*;
+pub use mapped::*;
+pub use vec::*;
+#[cfg(target_os = "linux")]
+pub use shm::*;
\ No newline at end of file
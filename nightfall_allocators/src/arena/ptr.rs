@@ -1,7 +1,7 @@
 #![allow(unused)]
 use std::{alloc::{Allocator, Layout}, cell::Cell, rc::Rc, sync::Arc};
 
-use crate::error::AllocError;
+use crate::{compose::Owns, error::AllocError};
 /// Represents an abstract arena allocator.
 /// # Concepts
 /// An arena allocator is useful for when you are going to allocate lots of scratch data 
@@ -30,7 +30,7 @@ use crate::error::AllocError;
 /// as an allocation is really fast.
 pub trait Arena {
     type Allocation;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError>;
     fn size(&self) -> usize;
     fn allocated(&self) -> usize;
     /// extremely dangerous function because calling this function and using a previously allocated value
@@ -42,7 +42,7 @@ pub trait Arena {
 
 impl<T: Arena> Arena for Arc<T> {
     type Allocation = T::Allocation;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
         (**self).arena_alloc(layout)
     }
     fn allocated(&self) -> usize {
@@ -62,7 +62,7 @@ impl<T: Arena> Arena for Arc<T> {
 }
 impl<T: Arena> Arena for Rc<T> {
     type Allocation = T::Allocation;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
         (**self).arena_alloc(layout)
     }
     fn allocated(&self) -> usize {
@@ -82,7 +82,7 @@ impl<T: Arena> Arena for Rc<T> {
 }
 impl<T: Arena> Arena for Box<T> {
     type Allocation = T::Allocation;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
         (**self).arena_alloc(layout)
     }
     fn allocated(&self) -> usize {
@@ -102,7 +102,7 @@ impl<T: Arena> Arena for Box<T> {
 }
 impl<T: Arena> Arena for &T {
     type Allocation = T::Allocation;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
         (**self).arena_alloc(layout)
     }
     fn allocated(&self) -> usize {
@@ -142,28 +142,34 @@ impl PtrArena {
     pub unsafe fn from_slice(slice: &mut [u8]) -> Self {
         Self { ptr: slice.as_mut_ptr(), size: slice.len(), offset: Cell::new(0) }
     }
-    pub fn from_arena(arena: &dyn Arena<Allocation = *mut u8>, layout: Layout) -> anyhow::Result<Self> {
+    pub fn from_arena(arena: &dyn Arena<Allocation = std::ptr::NonNull<[u8]>>, layout: Layout) -> Result<Self, AllocError> {
         let ptr = arena.arena_alloc(layout)?;
-        Ok(Self { ptr, size: layout.size(), offset: Cell::new(0) })
+        Ok(Self { ptr: ptr.as_ptr().cast(), size: layout.size(), offset: Cell::new(0) })
     }
     pub fn as_ptr(&self) -> *mut u8 {
         self.ptr
     }
+    /// moves the bump offset, used to restore snapshots.
+    pub(crate) unsafe fn set_allocated(&self, offset: usize) {
+        self.offset.set(offset);
+    }
 }
 
 impl Arena for PtrArena {
     type Allocation = std::ptr::NonNull<[u8]>;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
-        self.offset.set(self.offset.get().next_multiple_of(layout.align())); // align type
-        let offset = self.offset.get();
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        // align the address rather than the offset, the arena itself might not be aligned to the type
+        let available = self.size.saturating_sub(self.offset.get());
+        let address = self.ptr as usize + self.offset.get();
+        let offset = self.offset.get() + (address.next_multiple_of(layout.align()) - address); // align type
         if let Some(new_offset) = offset.checked_add(layout.size()) { // checks for addition overflow, allocation can not overflow
             if new_offset > self.size { // allocation too larg
-                Err(AllocError::OutOfMemory)?
+                Err(AllocError::OutOfMemory { requested: layout.size(), available })?
             }
             self.offset.set(new_offset);
             unsafe { Ok(std::ptr::NonNull::new(std::slice::from_raw_parts_mut(self.ptr.add(offset), layout.size())).unwrap()) }
         } else { // size too large, not enough space
-            Err(AllocError::OutOfMemory)?
+            Err(AllocError::OutOfMemory { requested: layout.size(), available })?
         }
     }
     fn size(&self) -> usize {
@@ -188,6 +194,11 @@ unsafe impl Allocator for PtrArena {
         // empty deallocate function, since we clear Arenas, not deallocate.
     }
 }
+impl Owns for PtrArena {
+    fn owns(&self, ptr: std::ptr::NonNull<u8>, _: Layout) -> bool {
+        (self.ptr as usize..self.ptr as usize + self.size).contains(&(ptr.as_ptr() as usize))
+    }
+}
 
 #[cfg(test)]
 mod test {
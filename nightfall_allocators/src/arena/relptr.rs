@@ -0,0 +1,60 @@
+use std::{fmt::Debug, marker::PhantomData, ptr::NonNull};
+
+/// Pointer stored as the distance from its own address to the target.
+/// # Concepts
+/// As long as the pointer and its target live in the same region, moving the whole region
+/// keeps the distance between them, so the pointer stays valid. This is what makes data
+/// structures inside a [`super::MappedFileArena`] usable after the file is mapped at a
+/// different address. A distance of 0 is used as null, a pointer can't point to itself.
+///
+/// Moving a `RelPtr` on its own changes its address and breaks it, that's why it
+/// isn't [`Clone`] or [`Copy`]. Use [`RelPtr::set`] to point another `RelPtr` at the same target.
+#[repr(transparent)]
+pub struct RelPtr<T> {
+    offset: isize,
+    marker_: PhantomData<*mut T>,
+}
+
+impl<T> RelPtr<T> {
+    pub const fn null() -> Self {
+        Self { offset: 0, marker_: PhantomData }
+    }
+    pub fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+    /// points at `target`, or null if it's `None`.
+    pub fn set(&mut self, target: Option<NonNull<T>>) {
+        self.offset = match target {
+            Some(target) => (target.as_ptr() as isize).wrapping_sub(self as *const Self as isize),
+            None => 0,
+        };
+    }
+    pub fn get(&self) -> Option<NonNull<T>> {
+        if self.is_null() {
+            return None;
+        }
+        NonNull::new((self as *const Self as *mut u8).wrapping_offset(self.offset).cast())
+    }
+    /// # Safety
+    /// The target must be a valid `T` that isn't mutably borrowed.
+    pub unsafe fn as_ref(&self) -> Option<&T> {
+        self.get().map(|ptr| unsafe { ptr.as_ref() })
+    }
+    /// # Safety
+    /// The target must be a valid `T` that isn't borrowed.
+    pub unsafe fn as_mut(&mut self) -> Option<&mut T> {
+        self.get().map(|mut ptr| unsafe { ptr.as_mut() })
+    }
+}
+
+impl<T> Default for RelPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> Debug for RelPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RelPtr").field("offset", &self.offset).field("target", &self.get()).finish()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,316 @@
+use std::{alloc::{Allocator, Layout}, ffi::CString, fs::File, os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd}, ptr::NonNull, sync::atomic::{AtomicU64, Ordering}, time::{Duration, Instant}};
+
+use memmap2::MmapMut;
+
+use crate::{compose::Owns, error::{AllocError, MappedError}};
+
+use super::Arena;
+
+const MAGIC: u64 = u64::from_le_bytes(*b"NFSHMAR1");
+/// how long [`ShmArena::open_named`] waits for another process to finish creating the arena.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Stored at the start of the shared region, everything after it belongs to the arena.
+#[repr(C)]
+struct ShmHeader {
+    /// written last, once the rest of the header is in place.
+    magic: AtomicU64,
+    size: u64,
+    /// bump offset from the start of the region, shared by every process.
+    offset: AtomicU64,
+    /// offset of the root object from the start of the region, 0 if there's none.
+    root: AtomicU64,
+}
+
+const HEADER_SIZE: usize = std::mem::size_of::<ShmHeader>();
+
+/// Arena in shared memory that several processes can allocate from at the same time.
+/// # Concepts
+/// The region is a `memfd_create` or `shm_open` file mapped with `MAP_SHARED`, and the bump
+/// offset lives in a header at its start. Allocating bumps that offset atomically, so producer
+/// and consumer processes can both allocate without a lock. Every process maps the region at
+/// its own address, structures exchanged through it have to link to each other with
+/// [`super::RelPtr`] and are found through [`ShmArena::root`] or [`ShmArena::offset_of`].
+///
+/// Anonymous arenas from [`ShmArena::create`] are shared with child processes by inheriting
+/// the mapping across `fork`, or by passing [`ShmArena::fd`] and calling [`ShmArena::from_fd`].
+/// Named arenas from [`ShmArena::open_named`] can be opened by unrelated processes.
+pub struct ShmArena {
+    map: MmapMut,
+    ptr: *mut u8,
+    size: usize,
+    fd: OwnedFd,
+}
+
+// every mutation of the shared state goes through the atomics of the header
+unsafe impl Send for ShmArena {}
+unsafe impl Sync for ShmArena {}
+
+impl ShmArena {
+    /// Creates an anonymous arena of `size` bytes with `memfd_create`. The descriptor is
+    /// inherited by child processes.
+    pub fn create(name: &str, size: usize) -> Result<Self, MappedError> {
+        let name = CString::new(name).map_err(|_| MappedError::InvalidFile("name contains a nul byte"))?;
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), 0) };
+        if fd < 0 {
+            Err(std::io::Error::last_os_error())?
+        }
+        Self::initialize(unsafe { OwnedFd::from_raw_fd(fd) }, size)
+    }
+    /// Opens the named arena `name` with `shm_open`, creating it with `size` bytes if it doesn't exist.
+    /// Names follow `shm_open`, e.g. `/pipeline`. If another process is still creating the arena,
+    /// this waits until its header is published.
+    pub fn open_named(name: &str, size: usize) -> Result<Self, MappedError> {
+        let name = CString::new(name).map_err(|_| MappedError::InvalidFile("name contains a nul byte"))?;
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR | libc::O_CREAT | libc::O_EXCL, 0o600) };
+        if fd >= 0 {
+            return Self::initialize(unsafe { OwnedFd::from_raw_fd(fd) }, size);
+        }
+        let error = std::io::Error::last_os_error();
+        if error.kind() != std::io::ErrorKind::AlreadyExists {
+            Err(error)?
+        }
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR, 0) };
+        if fd < 0 {
+            Err(std::io::Error::last_os_error())?
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        // the creator may not have sized the object or written the header yet
+        let deadline = Instant::now() + OPEN_TIMEOUT;
+        loop {
+            match Self::from_fd(fd.try_clone()?) {
+                Err(MappedError::InvalidFile(_)) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(1)),
+                result => return result,
+            }
+        }
+    }
+    /// Removes the named arena `name`, processes that have it open keep using it.
+    pub fn unlink_named(name: &str) -> Result<(), MappedError> {
+        let name = CString::new(name).map_err(|_| MappedError::InvalidFile("name contains a nul byte"))?;
+        if unsafe { libc::shm_unlink(name.as_ptr()) } < 0 {
+            Err(std::io::Error::last_os_error())?
+        }
+        Ok(())
+    }
+    /// Maps an arena created by another process, e.g. from a descriptor passed to a child.
+    pub fn from_fd(fd: OwnedFd) -> Result<Self, MappedError> {
+        let arena = Self::map(fd)?;
+        if arena.size < HEADER_SIZE {
+            Err(MappedError::InvalidFile("region too small to hold the header"))?
+        }
+        let header = arena.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            Err(MappedError::InvalidFile("not a shared arena"))?
+        }
+        let offset = header.offset.load(Ordering::Relaxed);
+        if header.size as usize != arena.size || !(HEADER_SIZE as u64..=header.size).contains(&offset) || header.root.load(Ordering::Relaxed) >= header.size {
+            Err(MappedError::InvalidFile("corrupted header"))?
+        }
+        Ok(arena)
+    }
+    fn initialize(fd: OwnedFd, size: usize) -> Result<Self, MappedError> {
+        if size <= HEADER_SIZE {
+            Err(MappedError::InvalidFile("size too small to hold the header"))?
+        }
+        File::from(fd.try_clone()?).set_len(size as u64)?;
+        let arena = Self::map(fd)?;
+        let header = ShmHeader { magic: AtomicU64::new(0), size: size as u64, offset: AtomicU64::new(HEADER_SIZE as u64), root: AtomicU64::new(0) };
+        unsafe { arena.ptr.cast::<ShmHeader>().write(header) };
+        // publishes the header to processes waiting in `open_named`
+        arena.header().magic.store(MAGIC, Ordering::Release);
+        Ok(arena)
+    }
+    fn map(fd: OwnedFd) -> Result<Self, MappedError> {
+        let mut map = unsafe { MmapMut::map_mut(&fd)? };
+        let ptr = map.as_mut_ptr();
+        let size = map.len();
+        Ok(Self { map, ptr, size, fd })
+    }
+    pub fn fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+    /// offset of `ptr` from the start of the region, the same in every process.
+    pub fn offset_of<T>(&self, ptr: NonNull<T>) -> Option<usize> {
+        let offset = (ptr.as_ptr() as usize).checked_sub(self.ptr as usize)?;
+        (offset < self.size).then_some(offset)
+    }
+    /// pointer to `offset` bytes from the start of the region in this process.
+    pub fn at_offset<T>(&self, offset: usize) -> Option<NonNull<T>> {
+        (offset < self.size).then(|| NonNull::new(unsafe { self.ptr.add(offset) }).unwrap().cast())
+    }
+    /// Publishes the entry point of the data in the arena to every process.
+    pub fn set_root<T>(&self, root: Option<NonNull<T>>) {
+        let offset = root.and_then(|root| self.offset_of(root)).unwrap_or(0);
+        self.header().root.store(offset as u64, Ordering::Release);
+    }
+    /// The entry point published with [`ShmArena::set_root`], it's up to the caller to use the same `T`.
+    pub fn root<T>(&self) -> Option<NonNull<T>> {
+        match self.header().root.load(Ordering::Acquire) {
+            0 => None,
+            offset => self.at_offset(offset as usize),
+        }
+    }
+    fn header(&self) -> &ShmHeader {
+        unsafe { &*self.ptr.cast::<ShmHeader>() }
+    }
+}
+
+impl AsRawFd for ShmArena {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Arena for ShmArena {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        let mut current = self.header().offset.load(Ordering::Relaxed) as usize;
+        loop {
+            let address = self.ptr as usize + current;
+            let offset = current + (address.next_multiple_of(layout.align()) - address);
+            let end = match offset.checked_add(layout.size()) {
+                Some(end) if end <= self.size => end,
+                _ => Err(AllocError::OutOfMemory { requested: layout.size(), available: self.size - current })?,
+            };
+            match self.header().offset.compare_exchange_weak(current as u64, end as u64, Ordering::Relaxed, Ordering::Relaxed) {
+                // `offset` is the end of the mapping for zero sized allocations in a full arena
+                Ok(_) => return Ok(NonNull::slice_from_raw_parts(NonNull::new(unsafe { self.ptr.add(offset) }).unwrap(), layout.size())),
+                Err(actual) => current = actual as usize,
+            }
+        }
+    }
+    fn size(&self) -> usize {
+        self.size - HEADER_SIZE
+    }
+    fn allocated(&self) -> usize {
+        self.header().offset.load(Ordering::Relaxed) as usize - HEADER_SIZE
+    }
+    /// Clears the arena for every process sharing it.
+    unsafe fn clear(&self) {
+        self.header().root.store(0, Ordering::Release);
+        self.header().offset.store(HEADER_SIZE as u64, Ordering::Release);
+    }
+    fn is_clear(&self) -> bool {
+        self.header().offset.load(Ordering::Relaxed) as usize == HEADER_SIZE
+    }
+}
+
+unsafe impl Allocator for ShmArena {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        <Self as Arena>::arena_alloc(self, layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+        // empty deallocate function, since we clear Arenas, not deallocate.
+    }
+}
+
+impl Owns for ShmArena {
+    fn owns(&self, ptr: NonNull<u8>, _: Layout) -> bool {
+        self.offset_of(ptr).is_some_and(|offset| offset >= HEADER_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::Layout, ptr::NonNull, sync::atomic::Ordering};
+
+    use crate::{arena::{Arena, RelPtr}, error::MappedError};
+
+    use super::{ShmArena, HEADER_SIZE};
+
+    #[repr(C)]
+    struct Message {
+        sender: u32,
+        value: u64,
+        next: RelPtr<Message>,
+    }
+    /// runs `child` in a forked process and returns its exit code.
+    fn fork(child: impl FnOnce() -> bool) -> i32 {
+        wait(spawn(child))
+    }
+    /// runs `child` in a forked process without waiting for it.
+    fn spawn(child: impl FnOnce() -> bool) -> libc::pid_t {
+        match unsafe { libc::fork() } {
+            0 => unsafe { libc::_exit(if child() { 0 } else { 1 }) },
+            pid => {
+                assert!(pid > 0, "Testing fork succeeded");
+                pid
+            }
+        }
+    }
+    /// exit code of the process `pid`.
+    fn wait(pid: libc::pid_t) -> i32 {
+        let mut status = 0;
+        unsafe { libc::waitpid(pid, &mut status, 0) };
+        libc::WEXITSTATUS(status)
+    }
+    #[test]
+    fn fork_test() {
+        let arena = ShmArena::create("nightfall-test", 1024*64).unwrap();
+        let parent = arena.arena_alloc(Layout::new::<u64>()).unwrap();
+        let status = fork(|| {
+            // the child builds a list and publishes it through the root
+            let mut previous: Option<NonNull<Message>> = None;
+            for value in 0..16 {
+                let Ok(message) = arena.arena_alloc(Layout::new::<Message>()) else { return false };
+                let message = message.cast::<Message>();
+                unsafe {
+                    message.write(Message { sender: std::process::id(), value, next: RelPtr::null() });
+                    (*message.as_ptr()).next.set(previous);
+                }
+                previous = Some(message);
+            }
+            arena.set_root(previous);
+            true
+        });
+        assert!(status == 0, "Testing the child process succeeded");
+        let mut values = Vec::new();
+        let mut current = arena.root::<Message>().map(|root| unsafe { root.as_ref() });
+        while let Some(message) = current {
+            assert!(message.sender != std::process::id(), "Testing the message came from the child");
+            values.push(message.value);
+            current = unsafe { message.next.as_ref() };
+        }
+        assert!(values.iter().copied().eq((0..16).rev()), "Testing the list built by the child is readable: {values:?}");
+        assert!(arena.allocated() == 8 + 16*std::mem::size_of::<Message>(), "Testing the child's allocations bumped the shared offset");
+        assert!(arena.offset_of(parent.cast::<u8>()).is_some(), "Testing offsets of parent allocations");
+    }
+    #[test]
+    fn fd_test() {
+        let arena = ShmArena::create("nightfall-test", 4096).unwrap();
+        let value = arena.arena_alloc(Layout::new::<u64>()).unwrap().cast::<u64>();
+        unsafe { value.write(42) };
+        arena.set_root(Some(value));
+        let other = ShmArena::from_fd(arena.fd().try_clone_to_owned().unwrap()).unwrap();
+        assert!(other.as_ptr() != arena.as_ptr(), "Testing the second mapping has its own address");
+        assert!(unsafe { other.root::<u64>().unwrap().read() } == 42, "Testing the second mapping sees the same data");
+        other.arena_alloc(Layout::new::<u64>()).unwrap();
+        assert!(arena.allocated() == 16, "Testing both mappings share the bump offset");
+        other.arena_alloc(Layout::array::<u8>(other.size() - other.allocated()).unwrap()).unwrap();
+        assert!(arena.arena_alloc(Layout::new::<()>()).is_ok(), "Testing zero sized allocations in a full arena");
+        for (offset, root) in [(0, 0), (4096 + 1, 0), (HEADER_SIZE as u64, 4096)] {
+            arena.header().offset.store(offset, Ordering::Relaxed);
+            arena.header().root.store(root, Ordering::Relaxed);
+            let error = ShmArena::from_fd(arena.fd().try_clone_to_owned().unwrap()).err();
+            assert!(matches!(error, Some(MappedError::InvalidFile(_))), "Testing a corrupted offset or root is rejected");
+        }
+    }    #[test]
+    fn concurrent_open_test() {
+        let name = format!("/nightfall-open-{}", std::process::id());
+        for _ in 0..20 {
+            let _ = ShmArena::unlink_named(&name);
+            // every process races to create the arena, the others have to wait for its header
+            let children: Vec<_> = (0..4).map(|_| spawn(|| {
+                ShmArena::open_named(&name, 4096).is_ok_and(|arena| arena.arena_alloc(Layout::new::<u64>()).is_ok())
+            })).collect();
+            let arena = ShmArena::open_named(&name, 4096).unwrap();
+            arena.arena_alloc(Layout::new::<u64>()).unwrap();
+            assert!(children.into_iter().all(|child| wait(child) == 0), "Testing every process opened the arena");
+            assert!(arena.allocated() == 5*8, "Testing every process allocated from the same arena");
+        }
+        ShmArena::unlink_named(&name).unwrap();
+    }
+}
\ No newline at end of file
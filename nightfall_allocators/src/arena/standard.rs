@@ -1,52 +1,369 @@
 #![allow(unused)]
-use std::{alloc::{Allocator, Global, Layout}, ptr::NonNull};
+use std::{alloc::{Allocator, Global, Layout}, fmt::Debug, hash::Hash, marker::PhantomData, ptr::NonNull};
+
+use crate::{compose::Owns, error::{AllocError, SnapshotError}};
 
 use super::{Arena, PtrArena};
 pub struct NextArenaHeader {
     arena: Option<PtrArena>,
+    /// layout the chunk holding this header was allocated with.
+    layout: Layout,
+    huge_pages: bool,
+}
+/// chunks are aligned to this, so values aligned up to it keep their alignment when a
+/// snapshot is restored into chunks at other addresses.
+const CHUNK_ALIGN: usize = 64;
+const HEADER_SIZE: usize = std::mem::size_of::<NextArenaHeader>().next_multiple_of(CHUNK_ALIGN);
+const SNAPSHOT_MAGIC: [u8; 8] = *b"NFSNAP01";
+const HUGE_PAGE_SIZE: usize = 2*1024*1024;
+
+/// How a [`StandardArena`] allocates its chunks.
+/// # Concepts
+/// Large arenas touch lots of pages, and with 4 KiB pages that means lots of TLB misses.
+/// [`ChunkOptions::with_huge_pages`] aligns and sizes chunks to 2 MiB and asks the kernel to
+/// back them with transparent huge pages. When the allocator can't provide aligned chunks or
+/// the kernel refuses the advice, the chunk is used as a regular one, which shows in [`ArenaStats`].
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::arena::{ChunkOptions, StandardArena};
+///
+/// let arena = StandardArena::with_options(1024*1024*8, ChunkOptions::default().with_huge_pages().with_prefault());
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkOptions {
+    huge_pages: bool,
+    prefault: bool,
+    zeroed: bool,
+}
+impl ChunkOptions {
+    /// 2 MiB aligned chunks advised with `MADV_HUGEPAGE`, only advised on linux.
+    pub fn with_huge_pages(mut self) -> Self {
+        self.huge_pages = true;
+        self
+    }
+    /// touches every page of a chunk when it's created, so allocating never page faults.
+    pub fn with_prefault(mut self) -> Self {
+        self.prefault = true;
+        self
+    }
+    /// zeroes chunks when they're created. Clearing the arena doesn't zero them again.
+    pub fn with_zeroed(mut self) -> Self {
+        self.zeroed = true;
+        self
+    }
+    pub fn huge_pages(&self) -> bool {
+        self.huge_pages
+    }
+    pub fn prefault(&self) -> bool {
+        self.prefault
+    }
+    pub fn zeroed(&self) -> bool {
+        self.zeroed
+    }
+}
+
+/// Chunk statistics of a [`StandardArena`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    pub chunks: usize,
+    pub size: usize,
+    pub allocated: usize,
+    /// chunks the kernel accepted the huge page advice for. Whether it actually backs them with
+    /// huge pages depends on its settings, see `/sys/kernel/mm/transparent_hugepage`.
+    pub huge_page_chunks: usize,
+}
+
+/// Position of a value in a [`StandardArena`], counted across all of its chunks.
+/// # Concepts
+/// Unlike a pointer, an offset doesn't depend on where the chunks live, so structures that link
+/// to each other through offsets stay valid when a snapshot is restored into a fresh arena.
+/// Offsets are resolved with [`StandardArena::resolve`].
+/// ```text
+/// chunk 0 (1024)       chunk 1 (8192)
+/// ┌────────────────────┬──────────────────────────────┐
+/// │ 0..1024            │ 1024..9216                   │
+/// └────────────────────┴──────────────────────────────┘
+/// ```
+pub struct ArenaOffset<T> {
+    offset: usize,
+    marker_: PhantomData<fn() -> T>,
+}
+impl<T> ArenaOffset<T> {
+    pub const fn new(offset: usize) -> Self {
+        Self { offset, marker_: PhantomData }
+    }
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    pub fn cast<U>(self) -> ArenaOffset<U> {
+        ArenaOffset::new(self.offset)
+    }
+}
+impl<T> Clone for ArenaOffset<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for ArenaOffset<T> {}
+impl<T> PartialEq for ArenaOffset<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+impl<T> Eq for ArenaOffset<T> {}
+impl<T> Hash for ArenaOffset<T> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.offset.hash(state);
+    }
+}
+impl<T> Debug for ArenaOffset<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ArenaOffset").field(&self.offset).finish()
+    }
 }
 pub struct StandardArena<A: Allocator> {
     arena: PtrArena,
     allocator: A,
+    options: ChunkOptions,
 }
 impl StandardArena<Global> {
     pub fn new(size: usize) -> Self {
         Self::new_in(std::alloc::Global, size)
     }
+    pub fn with_options(size: usize, options: ChunkOptions) -> Self {
+        Self::with_options_in(std::alloc::Global, size, options)
+    }
 }
 impl<A: Allocator> StandardArena<A> {
     pub fn new_in(allocator: A, size: usize) -> Self {
-        let arena = Self::allocate_arena(&allocator, size);
-        Self { arena, allocator }
+        Self::with_options_in(allocator, size, ChunkOptions::default())
+    }
+    pub fn with_options_in(allocator: A, size: usize, options: ChunkOptions) -> Self {
+        let arena = Self::allocate_arena(&allocator, size, options).unwrap();
+        Self { arena, allocator, options }
+    }
+    pub fn options(&self) -> ChunkOptions {
+        self.options
+    }
+    pub fn stats(&self) -> ArenaStats {
+        let mut stats = ArenaStats::default();
+        for chunk in self.chunks() {
+            stats.chunks += 1;
+            stats.size += chunk.size();
+            stats.allocated += chunk.allocated();
+            stats.huge_page_chunks += Self::get_arena_header(chunk).huge_pages as usize;
+        }
+        stats
+    }
+    /// Creates an arena with the same chunks as the one `snapshot` was taken of and restores it.
+    pub fn from_snapshot_in(allocator: A, snapshot: &[u8]) -> Result<Self, SnapshotError> {
+        let (chunks, _) = Self::parse_snapshot(snapshot)?;
+        let arena = Self::allocate_arena(&allocator, chunks[0].0, ChunkOptions::default())?;
+        let arena = Self { arena, allocator, options: ChunkOptions::default() };
+        unsafe { arena.restore(snapshot)? };
+        Ok(arena)
+    }
+    /// Returns the offset of `ptr` across the chunks of the arena, if it belongs to it.
+    pub fn offset_of<T>(&self, ptr: NonNull<T>) -> Option<ArenaOffset<T>> {
+        let mut start = 0;
+        for chunk in self.chunks() {
+            if chunk.owns(ptr.cast(), Layout::new::<u8>()) {
+                return Some(ArenaOffset::new(start + (ptr.as_ptr() as usize - chunk.as_ptr() as usize)));
+            }
+            start += chunk.size();
+        }
+        None
+    }
+    /// Turns an offset back into a pointer in this arena, it's up to the caller to use the same `T`.
+    pub fn resolve<T>(&self, offset: ArenaOffset<T>) -> Option<NonNull<T>> {
+        let mut start = 0;
+        for chunk in self.chunks() {
+            if offset.offset < start + chunk.size() {
+                return NonNull::new(unsafe { chunk.as_ptr().add(offset.offset - start) }.cast());
+            }
+            start += chunk.size();
+        }
+        None
+    }
+    /// Copies the allocated bytes and bump state of every chunk into a buffer.
+    /// # Concepts
+    /// Only the allocated part of each chunk is copied, so a snapshot is as large as what
+    /// the arena holds. Restoring with [`StandardArena::restore`] rolls every value in the
+    /// arena back at once.
+    /// ```text
+    /// magic | chunk count | (size, allocated) per chunk | allocated bytes of every chunk
+    /// ```
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        self.snapshot_into(&mut buffer);
+        buffer
     }
-    fn allocate_arena(allocator: &A, size: usize) -> PtrArena {
-        let layout = Layout::from_size_align(size+std::mem::size_of::<NextArenaHeader>(), 1).unwrap();
-        let allocation = allocator.allocate(layout).unwrap().as_ptr().cast::<u8>();
-        let arena = unsafe { PtrArena::from_raw(allocation.add(std::mem::size_of::<NextArenaHeader>()), layout.size()-std::mem::size_of::<NextArenaHeader>()) };
-        arena
+    /// Same as [`StandardArena::snapshot`], but reuses the capacity of `buffer`.
+    pub fn snapshot_into(&self, buffer: &mut Vec<u8>) {
+        buffer.clear();
+        buffer.extend_from_slice(&SNAPSHOT_MAGIC);
+        buffer.extend_from_slice(&(self.chunks().count() as u64).to_le_bytes());
+        for chunk in self.chunks() {
+            buffer.extend_from_slice(&(chunk.size() as u64).to_le_bytes());
+            buffer.extend_from_slice(&(chunk.allocated() as u64).to_le_bytes());
+        }
+        for chunk in self.chunks() {
+            buffer.reserve(chunk.allocated());
+            unsafe {
+                // copied rather than read as a slice, the padding between allocations is uninitialized
+                std::ptr::copy_nonoverlapping(chunk.as_ptr(), buffer.as_mut_ptr().add(buffer.len()), chunk.allocated());
+                buffer.set_len(buffer.len() + chunk.allocated());
+            }
+        }
+    }
+    /// Rolls the arena back to `snapshot`, allocating the chunks it's missing. The chunks the
+    /// arena already has must have the same sizes as in the snapshot, chunks the snapshot
+    /// doesn't have are cleared. Nothing is changed if the snapshot can't be restored.
+    /// # Safety
+    /// Like [`Arena::clear`], every allocation made since the snapshot was taken is invalidated.
+    /// Values are restored bit for bit, so pointers between them are only valid when restoring
+    /// into the arena the snapshot was taken of, otherwise use [`ArenaOffset`].
+    pub unsafe fn restore(&self, snapshot: &[u8]) -> Result<(), SnapshotError> {
+        let (chunks, data) = Self::parse_snapshot(snapshot)?;
+        let mut data = &snapshot[data..];
+        for (index, (chunk, &(size, _))) in self.chunks().zip(&chunks).enumerate() {
+            if chunk.size() != size {
+                Err(SnapshotError::Incompatible { chunk: index, expected: size, found: chunk.size() })?
+            }
+        }
+        // every missing chunk is allocated before any of them is linked, so a failure leaves the arena as it was
+        let mut missing = Vec::new();
+        for &(size, _) in chunks.iter().skip(self.chunks().count()) {
+            match Self::allocate_arena(&self.allocator, size, self.options) {
+                Ok(chunk) => missing.push(chunk),
+                Err(error) => {
+                    for chunk in &missing {
+                        self.drop_recurse_inner(chunk);
+                    }
+                    Err(error)?
+                }
+            }
+        }
+        let next = missing.into_iter().rev().fold(None, |next, chunk| {
+            Self::get_arena_header(&chunk).arena = next;
+            Some(chunk)
+        });
+        if next.is_some() {
+            Self::get_arena_header(self.chunks().last().unwrap()).arena = next;
+        }
+        let mut current = Some(&self.arena);
+        for &(_, allocated) in &chunks {
+            let chunk = current.unwrap();
+            unsafe {
+                std::ptr::copy_nonoverlapping(data.as_ptr(), chunk.as_ptr(), allocated);
+                chunk.set_allocated(allocated);
+            }
+            data = &data[allocated..];
+            current = Self::get_arena_header(chunk).arena.as_ref();
+        }
+        while let Some(chunk) = current {
+            unsafe { chunk.clear() };
+            current = Self::get_arena_header(chunk).arena.as_ref();
+        }
+        Ok(())
+    }
+    /// returns the size and allocated bytes of every chunk, and where the data that follows them starts.
+    fn parse_snapshot(snapshot: &[u8]) -> Result<(Vec<(usize, usize)>, usize), SnapshotError> {
+        let mut words = snapshot.get(SNAPSHOT_MAGIC.len()..).unwrap_or_default().chunks_exact(8).map(|word| u64::from_le_bytes(word.try_into().unwrap()) as usize);
+        if !snapshot.starts_with(&SNAPSHOT_MAGIC) {
+            Err(SnapshotError::Invalid("not an arena snapshot"))?
+        }
+        let count = words.next().ok_or(SnapshotError::Invalid("truncated"))?;
+        if count == 0 {
+            Err(SnapshotError::Invalid("no chunks"))?
+        }
+        let mut chunks = Vec::new();
+        for _ in 0..count {
+            let (Some(size), Some(allocated)) = (words.next(), words.next()) else {
+                Err(SnapshotError::Invalid("truncated"))?
+            };
+            if allocated > size {
+                Err(SnapshotError::Invalid("chunk allocated more than its size"))?
+            }
+            // big enough that the chunk couldn't be allocated, even rounded up to huge pages
+            if size.checked_add(HEADER_SIZE + HUGE_PAGE_SIZE).is_none_or(|size| size > isize::MAX as usize) {
+                Err(SnapshotError::Invalid("chunk too large"))?
+            }
+            chunks.push((size, allocated));
+        }
+        let data = SNAPSHOT_MAGIC.len() + 8 + count*16;
+        let allocated = chunks.iter().try_fold(0usize, |sum, (_, allocated)| sum.checked_add(*allocated));
+        if allocated != Some(snapshot.len() - data) {
+            Err(SnapshotError::Invalid("data doesn't match the chunks"))?
+        }
+        Ok((chunks, data))
+    }
+    fn chunks(&self) -> impl Iterator<Item = &PtrArena> {
+        std::iter::successors(Some(&self.arena), |arena| Self::get_arena_header(arena).arena.as_ref())
+    }
+    fn allocate_arena(allocator: &A, size: usize, options: ChunkOptions) -> Result<PtrArena, AllocError> {
+        let allocate = |layout| if options.zeroed {
+            allocator.allocate_zeroed(layout)
+        } else {
+            allocator.allocate(layout)
+        };
+        let size = size.checked_add(HEADER_SIZE).ok_or(AllocError::InvalidLayout)?;
+        // fall back to a regular chunk when the allocator can't align to huge pages, or the size can't be rounded up to them
+        let huge = options.huge_pages.then(|| {
+            let layout = Layout::from_size_align(size.checked_next_multiple_of(HUGE_PAGE_SIZE)?, HUGE_PAGE_SIZE).ok()?;
+            Some((allocate(layout).ok()?, layout))
+        }).flatten();
+        let (allocation, layout) = match huge {
+            Some(huge) => huge,
+            None => {
+                let layout = Layout::from_size_align(size, CHUNK_ALIGN)?;
+                let allocation = allocate(layout).map_err(|_|AllocError::OutOfMemory { requested: layout.size(), available: 0 })?;
+                (allocation, layout)
+            }
+        };
+        let allocation = allocation.as_ptr().cast::<u8>();
+        let huge_pages = huge.is_some() && unsafe { advise_huge_pages(allocation, layout.size()) };
+        if options.prefault {
+            for page in (0..layout.size()).step_by(4096) {
+                // writing zero keeps zeroed chunks zeroed
+                unsafe { allocation.add(page).write_volatile(0) };
+            }
+        }
+        unsafe { allocation.cast::<NextArenaHeader>().write(NextArenaHeader { arena: None, layout, huge_pages }) };
+        let arena = unsafe { PtrArena::from_raw(allocation.add(HEADER_SIZE), layout.size()-HEADER_SIZE) };
+        Ok(arena)
     }
     fn get_arena_header(arena: &PtrArena) -> &mut NextArenaHeader {
-        unsafe { arena.as_ptr().sub(std::mem::size_of::<NextArenaHeader>()).cast::<NextArenaHeader>().as_mut().unwrap() }
+        unsafe { arena.as_ptr().sub(HEADER_SIZE).cast::<NextArenaHeader>().as_mut().unwrap() }
     }
     fn drop_recurse_inner(&self, current_arena: &PtrArena) {
         if let Some(arena) = &Self::get_arena_header(current_arena).arena {
             self.drop_recurse_inner(arena)
         }
-        let dealloc = unsafe { NonNull::new(current_arena.as_ptr().sub(std::mem::size_of::<NextArenaHeader>())).unwrap() };
-        unsafe { self.deallocate(dealloc, Layout::from_size_align(current_arena.size()+std::mem::size_of::<NextArenaHeader>(), 1).unwrap()) };
+        let dealloc = unsafe { NonNull::new(current_arena.as_ptr().sub(HEADER_SIZE)).unwrap() };
+        unsafe { self.allocator.deallocate(dealloc, Self::get_arena_header(current_arena).layout) };
     }
     fn drop_recurse(&self) {
         let current_arena = &self.arena;
         if let Some(arena) = Self::get_arena_header(current_arena).arena.as_ref() {
             self.drop_recurse_inner(arena)
         }
-        let dealloc = unsafe { NonNull::new(current_arena.as_ptr().sub(std::mem::size_of::<NextArenaHeader>())).unwrap() };
-        unsafe { self.deallocate(dealloc, Layout::from_size_align(current_arena.size()+std::mem::size_of::<NextArenaHeader>(), 1).unwrap()) };
+        let dealloc = unsafe { NonNull::new(current_arena.as_ptr().sub(HEADER_SIZE)).unwrap() };
+        unsafe { self.allocator.deallocate(dealloc, Self::get_arena_header(current_arena).layout) };
     }
 }
+/// asks the kernel to back `size` bytes at `ptr` with transparent huge pages, returns whether it accepted.
+#[cfg(target_os = "linux")]
+unsafe fn advise_huge_pages(ptr: *mut u8, size: usize) -> bool {
+    unsafe { libc::madvise(ptr.cast(), size, libc::MADV_HUGEPAGE) == 0 }
+}
+#[cfg(not(target_os = "linux"))]
+unsafe fn advise_huge_pages(_: *mut u8, _: usize) -> bool {
+    false
+}
 impl<A: Allocator> Arena for StandardArena<A> {
     type Allocation = std::ptr::NonNull<[u8]>;
-    fn arena_alloc(&self, layout: Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
         let mut current_arena = &self.arena;
         loop {
             if let Ok(alloc) = current_arena.arena_alloc(layout) {
@@ -59,7 +376,10 @@ impl<A: Allocator> Arena for StandardArena<A> {
             } else {
                 // align the next allocation to a page and multiply it by 2 to 
                 // double space before having to allocate another arena
-                let arena = Self::allocate_arena(&self.allocator, (current_arena.size().next_multiple_of(4096)*2).max(layout.size()));
+                let size = (current_arena.size().next_multiple_of(4096)*2).max(layout.size());
+                let arena = Self::allocate_arena(&self.allocator, size, self.options).map_err(|_| {
+                    AllocError::OutOfMemory { requested: layout.size(), available: self.size() - self.allocated() }
+                })?;
                 header.arena = Some(arena);
             }
         }
@@ -117,4 +437,140 @@ unsafe impl<A: Allocator> Allocator for StandardArena<A> {
     unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
         // empty deallocate function, since we clear Arenas, not deallocate.
     }
+}
+impl<A: Allocator> Owns for StandardArena<A> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        let mut current_arena = Some(&self.arena);
+        while let Some(arena) = current_arena {
+            if arena.owns(ptr, layout) {
+                return true;
+            }
+            current_arena = Self::get_arena_header(arena).arena.as_ref();
+        }
+        false
+    }
+}
+
+impl StandardArena<Global> {
+    pub fn from_snapshot(snapshot: &[u8]) -> Result<Self, SnapshotError> {
+        Self::from_snapshot_in(Global, snapshot)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::{Global, Layout}, ptr::NonNull};
+
+    use crate::{arena::Arena, debug::FailingAllocator, error::{AllocError, SnapshotError}};
+
+    use super::{advise_huge_pages, ArenaOffset, ChunkOptions, StandardArena, HEADER_SIZE};
+
+    struct Node {
+        value: u64,
+        next: Option<ArenaOffset<Node>>,
+    }
+    /// builds a list of `count` nodes and returns the offset of its head.
+    fn build(arena: &StandardArena<Global>, count: u64) -> Option<ArenaOffset<Node>> {
+        let mut head = None;
+        for value in 0..count {
+            let node = arena.arena_alloc(Layout::new::<Node>()).unwrap().cast::<Node>();
+            unsafe { node.write(Node { value, next: head }) };
+            head = arena.offset_of(node);
+        }
+        head
+    }
+    fn values(arena: &StandardArena<Global>, head: Option<ArenaOffset<Node>>) -> Vec<u64> {
+        let mut values = Vec::new();
+        let mut current = head;
+        while let Some(offset) = current {
+            let node = unsafe { arena.resolve(offset).unwrap().as_ref() };
+            values.push(node.value);
+            current = node.next;
+        }
+        values
+    }
+    #[test]
+    fn snapshot_test() {
+        let arena = StandardArena::new(64);
+        let head = build(&arena, 8);
+        let snapshot = arena.snapshot();
+        let (allocated, size) = (arena.allocated(), arena.size());
+        assert!(arena.size() > 64, "Testing the list spans several chunks");
+        // mutate everything after the snapshot
+        let mut current = head;
+        while let Some(offset) = current {
+            let mut node: NonNull<Node> = arena.resolve(offset).unwrap();
+            unsafe { node.as_mut().value += 100 };
+            current = unsafe { node.as_ref().next };
+        }
+        arena.arena_alloc(Layout::new::<[u8; 1024*16]>()).unwrap();
+        unsafe { arena.restore(&snapshot).unwrap() };
+        assert!(values(&arena, head) == [7, 6, 5, 4, 3, 2, 1, 0], "Testing the values were rolled back");
+        assert!(arena.allocated() == allocated, "Testing the bump state was rolled back");
+        let fresh = StandardArena::from_snapshot(&snapshot).unwrap();
+        assert!(values(&fresh, head) == [7, 6, 5, 4, 3, 2, 1, 0], "Testing the snapshot restores into a fresh arena");
+        assert!(fresh.allocated() == allocated && fresh.size() == size, "Testing the fresh arena has the snapshot's chunks");
+    }
+    #[test]
+    fn invalid_snapshot_test() {
+        let arena = StandardArena::new(64);
+        build(&arena, 1);
+        let snapshot = arena.snapshot();
+        let other = StandardArena::new(128);
+        let error = unsafe { other.restore(&snapshot).unwrap_err() };
+        assert!(error == SnapshotError::Incompatible { chunk: 0, expected: 64, found: 128 }, "Testing chunks of another size are rejected");
+        let error = unsafe { arena.restore(&snapshot[..snapshot.len() - 1]).unwrap_err() };
+        assert!(matches!(error, SnapshotError::Invalid(_)), "Testing truncated snapshots are rejected");
+        // one chunk of usize::MAX bytes holding usize::MAX bytes, then a second one holding 1
+        let mut huge = snapshot[..8].to_vec();
+        for word in [2, u64::MAX, u64::MAX, 1, 1] {
+            huge.extend_from_slice(&word.to_le_bytes());
+        }
+        let error = StandardArena::from_snapshot(&huge).err().unwrap();
+        assert!(matches!(error, SnapshotError::Invalid(_)), "Testing chunks too large to allocate are rejected");
+        let error = unsafe { arena.restore(&huge).unwrap_err() };
+        assert!(matches!(error, SnapshotError::Invalid(_)), "Testing chunks too large to allocate are rejected");
+        assert!(StandardArena::<Global>::allocate_arena(&Global, usize::MAX, ChunkOptions::default()).err() == Some(AllocError::InvalidLayout), "Testing chunk sizes that overflow are rejected");
+    }
+    #[test]
+    fn failed_restore_test() {
+        let arena = StandardArena::new(64);
+        build(&arena, 8);
+        arena.arena_alloc(Layout::new::<[u8; 1024*16]>()).unwrap();
+        let snapshot = arena.snapshot();
+        assert!(arena.stats().chunks > 2, "Testing the snapshot has several chunks to allocate");
+        // the first chunk is allocation 0, so the second missing chunk fails
+        let target = StandardArena::new_in(FailingAllocator::fail_nth(Global, 2), 64);
+        target.arena_alloc(Layout::new::<u64>()).unwrap();
+        let error = unsafe { target.restore(&snapshot).unwrap_err() };
+        assert!(matches!(error, SnapshotError::Alloc(AllocError::OutOfMemory { .. })), "Testing the failed allocation is reported");
+        assert!(target.stats().chunks == 1 && target.allocated() == 8, "Testing the arena was left unchanged");
+        assert!(target.allocator.live_bytes() == StandardArena::<Global>::get_arena_header(&target.arena).layout.size(), "Testing the chunks allocated before the failure were freed");
+    }
+    #[test]
+    fn options_test() {
+        let arena = StandardArena::with_options(1024, ChunkOptions::default().with_huge_pages().with_prefault().with_zeroed());
+        let stats = arena.stats();
+        assert!(stats.chunks == 1 && stats.size == 2*1024*1024 - 64, "Testing the chunk was sized to a huge page: {stats:?}");
+        assert!(arena.arena.as_ptr() as usize % (2*1024*1024) == 64, "Testing the chunk was aligned to a huge page");
+        let bytes = arena.arena_alloc(Layout::new::<[u8; 4096]>()).unwrap();
+        assert!(unsafe { bytes.as_ref() }.iter().all(|&byte| byte == 0), "Testing the chunk was zeroed");
+        arena.arena_alloc(Layout::new::<[u8; 1024*1024*4]>()).unwrap();
+        let stats = arena.stats();
+        assert!(stats.chunks == 2, "Testing a second chunk was added: {stats:?}");
+        for chunk in arena.chunks() {
+            let layout = StandardArena::<Global>::get_arena_header(chunk).layout;
+            assert!(layout.align() == 2*1024*1024 && layout.size().is_multiple_of(2*1024*1024), "Testing every chunk was sized to huge pages: {layout:?}");
+            assert!(chunk.as_ptr() as usize % (2*1024*1024) == 64, "Testing every chunk was aligned to a huge page");
+        }
+        // advising the same range again gives the same answer as when the chunks were allocated
+        let advised = arena.chunks().filter(|chunk| unsafe {
+            advise_huge_pages(chunk.as_ptr().sub(HEADER_SIZE), StandardArena::<Global>::get_arena_header(chunk).layout.size())
+        }).count();
+        assert!(stats.huge_page_chunks == advised, "Testing chunks report whether the kernel took the advice: {stats:?}");
+        // too large to round up to huge pages, which only matters when they were asked for
+        let size = isize::MAX as usize - 2*1024*1024;
+        let error = StandardArena::<Global>::allocate_arena(&Global, size, ChunkOptions::default()).err();
+        assert!(matches!(error, Some(AllocError::OutOfMemory { .. })), "Testing the huge page layout is only checked for huge pages: {error:?}");
+    }
 }
\ No newline at end of file
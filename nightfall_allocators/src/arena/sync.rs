@@ -1,8 +1,10 @@
-use std::{alloc::{Allocator, Global}, ops::Deref, sync::Arc};
+use std::{alloc::{Allocator, Global, Layout}, ops::Deref, ptr::NonNull, sync::Arc};
 
 use thread_local::ThreadLocal;
 
-use super::{Arena, StandardArena};
+use crate::error::AllocError;
+
+use super::{Arena, ArenaStats, ChunkOptions, StandardArena};
 #[repr(transparent)]
 struct SendSyncStandardArena<A: Allocator + Send + Sync>(StandardArena<A>);
 impl<A: Allocator + Send + Sync> Deref for SendSyncStandardArena<A> {
@@ -19,28 +21,41 @@ pub struct AsyncArena<A: Allocator + Send + Sync + Clone = Global> {
     thread_local: ThreadLocal<SendSyncStandardArena<A>>,
     start_size: usize,
     alloc: A,
+    options: ChunkOptions,
 }
 impl AsyncArena {
     pub fn new(start_size: usize) -> Arc<Self> {
         // align start_size to page size
-        Arc::new(Self { thread_local: ThreadLocal::new(), start_size: start_size.next_multiple_of(4096), alloc: Global })
+        Self::with_options(start_size, ChunkOptions::default())
+    }
+    /// every thread's arena allocates its chunks with `options`.
+    pub fn with_options(start_size: usize, options: ChunkOptions) -> Arc<Self> {
+        // align start_size to page size
+        Arc::new(Self { thread_local: ThreadLocal::new(), start_size: start_size.next_multiple_of(4096), alloc: Global, options })
     }
 }
 
 impl<A: Allocator + Send + Sync + Clone> AsyncArena<A> {
     pub fn new_in(alloc: A, start_size: usize) -> Self {
-        Self { thread_local: ThreadLocal::new(), start_size, alloc }
+        Self::with_options_in(alloc, start_size, ChunkOptions::default())
+    }
+    pub fn with_options_in(alloc: A, start_size: usize, options: ChunkOptions) -> Self {
+        Self { thread_local: ThreadLocal::new(), start_size, alloc, options }
+    }
+    /// stats of the calling thread's arena.
+    pub fn stats(&self) -> ArenaStats {
+        self.get_arena().stats()
     }
     fn get_arena(&self) -> &SendSyncStandardArena<A> {
         self.thread_local.get_or(||{
-            SendSyncStandardArena(StandardArena::new_in(self.alloc.clone(), self.start_size))
+            SendSyncStandardArena(StandardArena::with_options_in(self.alloc.clone(), self.start_size, self.options))
         })
     }
 }
 
 impl<A: Allocator + Send + Sync + Clone> Arena for  AsyncArena<A> {
     type Allocation = std::ptr::NonNull<[u8]>;
-    fn arena_alloc(&self, layout: std::alloc::Layout) -> anyhow::Result<Self::Allocation> {
+    fn arena_alloc(&self, layout: std::alloc::Layout) -> Result<Self::Allocation, AllocError> {
         self.get_arena().arena_alloc(layout)
     }
     fn allocated(&self) -> usize {
@@ -57,4 +72,12 @@ impl<A: Allocator + Send + Sync + Clone> Arena for  AsyncArena<A> {
     fn size(&self) -> usize {
         self.get_arena().size()
     }
+}
+unsafe impl<A: Allocator + Send + Sync + Clone> Allocator for AsyncArena<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        <Self as Arena>::arena_alloc(self, layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+        // empty deallocate function, since we clear Arenas, not deallocate.
+    }
 }
\ No newline at end of file
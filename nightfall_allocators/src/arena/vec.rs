@@ -0,0 +1,204 @@
+use std::{alloc::Layout, fmt::Debug, marker::PhantomData, mem::ManuallyDrop, ops::{Deref, DerefMut}, ptr::NonNull};
+
+use crate::error::AllocError;
+
+use super::{Arena, PtrArena};
+
+/// Growable vector that lives in an [`Arena`] and grows in place while it's at the top of it.
+/// # Concepts
+/// A bump allocator can't grow an allocation, but when nothing was allocated after the vector,
+/// the bytes right after it are the next ones the arena hands out. Growing allocates those
+/// bytes and keeps the elements where they are.
+/// ```text
+/// ┌────────┬────────────┬─────────────────┐
+/// │ other  │ vec        │ free            │   vec is at the top, grows in place
+/// └────────┴────────────┴─────────────────┘
+/// ┌────────┬────────────┬───────┬─────────┐
+/// │ other  │ vec        │ other │ free    │   something was allocated after it, relocates
+/// └────────┴────────────┴───────┴─────────┘
+/// ```
+/// Relocating leaves the old buffer in the arena until it's cleared. Once the vector is done
+/// growing, [`BumpVec::into_bump_slice`] turns it into a slice that lives as long as the arena.
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::arena::{BumpVec, PtrArena};
+///
+/// let mut memory = [0u8; 1024];
+/// let arena = unsafe { PtrArena::from_slice(&mut memory) };
+/// let mut vector = BumpVec::new_in(&arena);
+/// vector.extend(0..16u32);
+/// let slice: &[u32] = vector.into_bump_slice();
+/// assert!(slice.len() == 16);
+/// ```
+pub struct BumpVec<'a, T, R: Arena<Allocation = NonNull<[u8]>> = PtrArena> {
+    ptr: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    arena: &'a R,
+    /// `allocated()` of the arena right after the buffer was last allocated or grown,
+    /// the vector is still at the top of the arena as long as it didn't change.
+    top: usize,
+    marker_: PhantomData<T>,
+}
+
+impl<'a, T, R: Arena<Allocation = NonNull<[u8]>>> BumpVec<'a, T, R> {
+    pub fn new_in(arena: &'a R) -> Self {
+        let capacity = if std::mem::size_of::<T>() == 0 { usize::MAX } else { 0 };
+        Self { ptr: NonNull::dangling(), len: 0, capacity, arena, top: 0, marker_: PhantomData }
+    }
+    pub fn with_capacity_in(capacity: usize, arena: &'a R) -> Result<Self, AllocError> {
+        let mut vector = Self::new_in(arena);
+        vector.try_reserve(capacity)?;
+        Ok(vector)
+    }
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn arena(&self) -> &'a R {
+        self.arena
+    }
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+    /// Makes room for at least `additional` more elements, in place if the vector is at the top of the arena.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let required = self.len.checked_add(additional).ok_or(AllocError::InvalidLayout)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+        let capacity = required.max(self.capacity*2).max(4);
+        let layout = Layout::array::<T>(capacity)?;
+        if self.capacity > 0 && self.arena.allocated() == self.top {
+            let end = unsafe { self.ptr.add(self.capacity) }.cast::<u8>();
+            let extension = Layout::from_size_align(layout.size() - self.capacity*std::mem::size_of::<T>(), 1)?;
+            // the arena could still place the extension elsewhere, e.g. in a new chunk, and it's lost then
+            if let Ok(extension) = self.arena.arena_alloc(extension) && extension.cast::<u8>() == end {
+                self.capacity = capacity;
+                self.top = self.arena.allocated();
+                return Ok(());
+            }
+        }
+        let ptr = self.arena.arena_alloc(layout)?.cast::<T>();
+        unsafe { ptr.copy_from_nonoverlapping(self.ptr, self.len) };
+        self.ptr = ptr;
+        self.capacity = capacity;
+        self.top = self.arena.allocated();
+        Ok(())
+    }
+    /// # Panics
+    /// Panics if the arena runs out of memory.
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(error) = self.try_reserve(additional) {
+            panic!("{error}");
+        }
+    }
+    /// # Panics
+    /// Panics if the arena runs out of memory.
+    pub fn push(&mut self, value: T) {
+        self.reserve(1);
+        unsafe { self.ptr.add(self.len).write(value) };
+        self.len += 1;
+    }
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(unsafe { self.ptr.add(self.len).read() })
+    }
+    /// Drops every element and keeps the capacity.
+    pub fn clear(&mut self) {
+        let elements = NonNull::slice_from_raw_parts(self.ptr, self.len);
+        self.len = 0;
+        unsafe { elements.drop_in_place() };
+    }
+    /// Freezes the vector into a slice that lives as long as the arena. The elements are never
+    /// dropped, since the arena doesn't drop what it holds.
+    pub fn into_bump_slice(self) -> &'a mut [T] {
+        let vector = ManuallyDrop::new(self);
+        unsafe { std::slice::from_raw_parts_mut(vector.ptr.as_ptr(), vector.len) }
+    }
+}
+
+impl<T, R: Arena<Allocation = NonNull<[u8]>>> Deref for BumpVec<'_, T, R> {
+    type Target = [T];
+    fn deref(&self) -> &Self::Target {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, R: Arena<Allocation = NonNull<[u8]>>> DerefMut for BumpVec<'_, T, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl<T, R: Arena<Allocation = NonNull<[u8]>>> Extend<T> for BumpVec<'_, T, R> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T: Debug, R: Arena<Allocation = NonNull<[u8]>>> Debug for BumpVec<'_, T, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, R: Arena<Allocation = NonNull<[u8]>>> Drop for BumpVec<'_, T, R> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::Layout, rc::Rc};
+
+    use crate::arena::{Arena, PtrArena, StandardArena};
+
+    use super::BumpVec;
+    #[test]
+    fn in_place_test() {
+        let mut memory = vec![0u8; 1024*4];
+        let arena = unsafe { PtrArena::from_slice(&mut memory) };
+        let mut vector = BumpVec::new_in(&arena);
+        vector.push(0u64);
+        let ptr = vector.as_ptr();
+        vector.extend(1..100);
+        assert!(vector.as_ptr() == ptr, "Testing the vector grew in place");
+        assert!(arena.allocated() == vector.capacity()*8, "Testing growing didn't waste memory");
+        arena.arena_alloc(Layout::new::<u8>()).unwrap();
+        vector.extend(100..200);
+        assert!(vector.as_ptr() != ptr, "Testing the vector relocated once it wasn't at the top");
+        assert!(vector.iter().copied().eq(0..200), "Testing relocating kept the elements");
+        let slice = vector.into_bump_slice();
+        slice[0] = 42;
+        assert!(slice.len() == 200 && slice[0] == 42, "Testing the slice outlives the vector");
+    }
+    #[test]
+    fn drop_test() {
+        let arena = StandardArena::new(64);
+        let counter = Rc::new(());
+        let mut vector = BumpVec::new_in(&arena);
+        for _ in 0..32 {
+            vector.push(counter.clone());
+        }
+        assert!(vector.pop().is_some() && Rc::strong_count(&counter) == 32, "Testing pop gives the element back");
+        drop(vector);
+        assert!(Rc::strong_count(&counter) == 1, "Testing dropping the vector drops its elements");
+        let mut zsts = BumpVec::new_in(&arena);
+        zsts.extend(std::iter::repeat_n((), 1000));
+        assert!(zsts.len() == 1000 && zsts.capacity() == usize::MAX, "Testing zero sized types don't allocate");
+    }
+}
\ No newline at end of file
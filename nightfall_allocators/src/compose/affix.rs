@@ -0,0 +1,184 @@
+use std::{alloc::{AllocError, Allocator, Layout}, marker::PhantomData, mem::ManuallyDrop, ptr::NonNull};
+
+use super::Owns;
+
+/// Stores a `Prefix` in front of and a `Suffix` behind every allocation.
+/// # Concepts
+/// Both are created with [`Default`] when the allocation is made and dropped when it's
+/// deallocated, and can be reached from the allocation with [`AffixAllocator::prefix`]
+/// and [`AffixAllocator::suffix`]. Useful for per allocation metadata like reference
+/// counts, sizes or tags, without changing the allocated type. Growing or shrinking an
+/// allocation keeps its prefix and moves its suffix to the new end.
+/// ```text
+/// ┌────────┬─────────┬─────────┬────────┐
+/// │ Prefix │ padding │ payload │ Suffix │
+/// └────────┴─────────┴─────────┴────────┘
+/// ```
+pub struct AffixAllocator<A, Prefix = (), Suffix = ()> {
+    inner: A,
+    marker_: PhantomData<(Prefix, Suffix)>,
+}
+
+impl<A, Prefix, Suffix> AffixAllocator<A, Prefix, Suffix> {
+    pub fn new(inner: A) -> Self {
+        Self { inner, marker_: PhantomData }
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    /// Returns the prefix stored in front of an allocation.
+    /// # Safety
+    /// `ptr` must have been allocated by this allocator with `layout` and not deallocated yet.
+    pub unsafe fn prefix(&self, ptr: NonNull<u8>, layout: Layout) -> NonNull<Prefix> {
+        let (_, payload, _) = Self::affixed_layout(layout).unwrap();
+        unsafe { ptr.sub(payload).cast() }
+    }
+    /// Returns the suffix stored behind an allocation.
+    /// # Safety
+    /// `ptr` must have been allocated by this allocator with `layout` and not deallocated yet.
+    pub unsafe fn suffix(&self, ptr: NonNull<u8>, layout: Layout) -> NonNull<Suffix> {
+        let (_, payload, suffix) = Self::affixed_layout(layout).unwrap();
+        unsafe { ptr.sub(payload).add(suffix).cast() }
+    }
+    /// layout of the underlying allocation, with the offsets of the payload and the suffix.
+    fn affixed_layout(layout: Layout) -> Option<(Layout, usize, usize)> {
+        let (with_payload, payload) = Layout::new::<Prefix>().extend(layout).ok()?;
+        let (affixed, suffix) = with_payload.extend(Layout::new::<Suffix>()).ok()?;
+        Some((affixed, payload, suffix))
+    }
+    /// moves an allocation to `new_layout`, keeping the prefix and rewriting the suffix behind the new payload.
+    /// `resize` is used when the payload stays at the same offset, otherwise the allocation is copied over.
+    unsafe fn reallocate(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+        zeroed: bool,
+        resize: impl FnOnce(NonNull<u8>, Layout, Layout) -> Result<NonNull<[u8]>, AllocError>,
+    ) -> Result<NonNull<[u8]>, AllocError> where A: Allocator {
+        let (old_affixed, old_payload, old_suffix) = Self::affixed_layout(old_layout).ok_or(AllocError)?;
+        let (new_affixed, new_payload, new_suffix) = Self::affixed_layout(new_layout).ok_or(AllocError)?;
+        unsafe {
+            let old_base = ptr.sub(old_payload);
+            // only a copy until the move succeeds, the old allocation still owns the suffix on failure
+            let suffix = ManuallyDrop::new(old_base.add(old_suffix).cast::<Suffix>().read());
+            let base = if old_payload == new_payload {
+                resize(old_base, old_affixed, new_affixed)?.cast::<u8>()
+            } else {
+                let base = self.inner.allocate(new_affixed)?.cast::<u8>();
+                base.cast::<Prefix>().write(old_base.cast::<Prefix>().read());
+                base.add(new_payload).copy_from_nonoverlapping(ptr, old_layout.size().min(new_layout.size()));
+                self.inner.deallocate(old_base, old_affixed);
+                base
+            };
+            if zeroed {
+                base.add(new_payload + old_layout.size()).write_bytes(0, new_layout.size() - old_layout.size());
+            }
+            base.add(new_suffix).cast::<Suffix>().write(ManuallyDrop::into_inner(suffix));
+            Ok(NonNull::slice_from_raw_parts(base.add(new_payload), new_layout.size()))
+        }
+    }
+}
+
+unsafe impl<A: Allocator, Prefix: Default, Suffix: Default> Allocator for AffixAllocator<A, Prefix, Suffix> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let (affixed, payload, suffix) = Self::affixed_layout(layout).ok_or(AllocError)?;
+        let base = self.inner.allocate(affixed)?.cast::<u8>();
+        unsafe {
+            base.cast::<Prefix>().write(Prefix::default());
+            base.add(suffix).cast::<Suffix>().write(Suffix::default());
+            Ok(NonNull::slice_from_raw_parts(base.add(payload), layout.size()))
+        }
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let (affixed, payload, suffix) = Self::affixed_layout(layout).unwrap();
+        unsafe {
+            let base = ptr.sub(payload);
+            base.cast::<Prefix>().drop_in_place();
+            base.add(suffix).cast::<Suffix>().drop_in_place();
+            self.inner.deallocate(base, affixed);
+        }
+    }
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { self.reallocate(ptr, old_layout, new_layout, false, |ptr, old, new| self.inner.grow(ptr, old, new)) }
+    }
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        // the old suffix ends up inside the grown payload, so the new bytes are zeroed by hand
+        unsafe { self.reallocate(ptr, old_layout, new_layout, true, |ptr, old, new| self.inner.grow(ptr, old, new)) }
+    }
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        unsafe { self.reallocate(ptr, old_layout, new_layout, false, |ptr, old, new| self.inner.shrink(ptr, old, new)) }
+    }
+}
+
+impl<A: Owns, Prefix, Suffix> Owns for AffixAllocator<A, Prefix, Suffix> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        match Self::affixed_layout(layout) {
+            Some((affixed, payload, _)) => self.inner.owns(unsafe { ptr.sub(payload) }, affixed),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Global, Layout};
+
+    use super::AffixAllocator;
+    #[test]
+    fn affix_test() {
+        let allocator = AffixAllocator::<_, u64, u32>::new(Global);
+        let layout = Layout::new::<[u8; 3]>();
+        let allocation = allocator.allocate(layout).unwrap();
+        assert!(allocation.len() == 3, "Testing the payload has the requested size");
+        unsafe {
+            allocation.cast::<[u8; 3]>().write([1, 2, 3]);
+            allocator.prefix(allocation.cast(), layout).write(u64::MAX);
+            allocator.suffix(allocation.cast(), layout).write(u32::MAX);
+            assert!(allocation.cast::<[u8; 3]>().read() == [1, 2, 3], "Testing the affixes don't overlap the payload");
+            assert!(allocator.prefix(allocation.cast(), layout).read() == u64::MAX, "Testing the prefix is kept");
+            allocator.deallocate(allocation.cast(), layout);
+        }
+        let mut boxed = Box::new_in(7u128, &allocator);
+        *boxed += 1;
+        let ptr = std::ptr::NonNull::from(&*boxed).cast::<u8>();
+        assert!(unsafe { allocator.prefix(ptr, Layout::new::<u128>()).read() } == 0, "Testing affixes start out as default");
+        assert!((ptr.as_ptr() as usize).is_multiple_of(std::mem::align_of::<u128>()), "Testing the payload stays aligned");
+    }
+    #[test]
+    fn resize_test() {
+        let allocator = AffixAllocator::<_, u64, u32>::new(Global);
+        let affixes = |vector: &Vec<u16, &AffixAllocator<Global, u64, u32>>| unsafe {
+            let layout = Layout::array::<u16>(vector.capacity()).unwrap();
+            let ptr = std::ptr::NonNull::new_unchecked(vector.as_ptr() as *mut u8);
+            (allocator.prefix(ptr, layout).read(), allocator.suffix(ptr, layout).read())
+        };
+        let mut vector = Vec::with_capacity_in(4, &allocator);
+        vector.extend(0..4u16);
+        unsafe {
+            let layout = Layout::array::<u16>(vector.capacity()).unwrap();
+            let ptr = std::ptr::NonNull::new_unchecked(vector.as_mut_ptr()).cast();
+            allocator.prefix(ptr, layout).write(0xdead_beef);
+            allocator.suffix(ptr, layout).write(7);
+        }
+        vector.extend(4..1000);
+        assert!(affixes(&vector) == (0xdead_beef, 7), "Testing growing keeps the prefix and moves the suffix");
+        assert!(vector.iter().copied().eq(0..1000), "Testing growing keeps the payload");
+        vector.truncate(10);
+        vector.shrink_to_fit();
+        assert!(affixes(&vector) == (0xdead_beef, 7), "Testing shrinking keeps the affixes");
+        assert!(vector.iter().copied().eq(0..10), "Testing shrinking keeps the payload");
+        unsafe {
+            let layout = Layout::array::<u8>(3).unwrap();
+            let ptr = allocator.allocate(layout).unwrap().cast::<u8>();
+            ptr.write_bytes(1, 3);
+            allocator.prefix(ptr, layout).write(3);
+            let grown = Layout::from_size_align(64, 32).unwrap();
+            let ptr = allocator.grow_zeroed(ptr, layout, grown).unwrap();
+            assert!(ptr.as_ref()[..3] == [1; 3] && ptr.as_ref()[3..].iter().all(|&byte| byte == 0), "Testing growing zeroed clears the new bytes");
+            assert!((ptr.cast::<u8>().as_ptr() as usize).is_multiple_of(32), "Testing a realigned payload");
+            assert!(allocator.prefix(ptr.cast(), grown).read() == 3, "Testing realigning keeps the prefix");
+            allocator.deallocate(ptr.cast(), grown);
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,86 @@
+use std::{alloc::{Allocator, Layout}, ptr::NonNull};
+
+use super::Owns;
+
+/// Splits the sizes `MIN..=MAX` into buckets of `STEP` bytes, each served by its own allocator.
+/// # Concepts
+/// Bucket `i` serves the sizes up to `MIN + (i+1)*STEP`, so an allocator that works best
+/// with similar sizes, like a [`crate::heap::FreeListAllocator`], only ever sees sizes that
+/// are at most `STEP` bytes apart. Allocations outside of `MIN..=MAX` fail, pair it with a
+/// [`super::Segregator`] or [`super::Fallback`] to handle them.
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::{arena::StandardArena, compose::Bucketizer};
+///
+/// // 4 arenas for 1..=64, 65..=128, 129..=192 and 193..=256 bytes
+/// let allocator = Bucketizer::<_, 0, 256, 64>::new(|_| StandardArena::new(4096));
+/// assert!(allocator.buckets().len() == 4);
+/// ```
+pub struct Bucketizer<A, const MIN: usize, const MAX: usize, const STEP: usize> {
+    buckets: Box<[A]>,
+}
+
+impl<A, const MIN: usize, const MAX: usize, const STEP: usize> Bucketizer<A, MIN, MAX, STEP> {
+    const COUNT: usize = {
+        assert!(STEP > 0 && MIN <= MAX, "Bucketizer needs a non zero step and MIN <= MAX");
+        if MIN == MAX { 1 } else { (MAX - MIN).div_ceil(STEP) }
+    };
+    /// creates every bucket, `factory` receives the largest size the bucket serves.
+    pub fn new(mut factory: impl FnMut(usize) -> A) -> Self {
+        let buckets = (0..Self::COUNT).map(|bucket| factory((MIN + (bucket + 1)*STEP).min(MAX))).collect();
+        Self { buckets }
+    }
+    pub fn buckets(&self) -> &[A] {
+        &self.buckets
+    }
+    /// bucket serving allocations of `size` bytes.
+    pub fn bucket(&self, size: usize) -> Option<&A> {
+        if !(MIN..=MAX).contains(&size) {
+            return None;
+        }
+        self.buckets.get((size - MIN).saturating_sub(1) / STEP)
+    }
+}
+
+unsafe impl<A: Allocator, const MIN: usize, const MAX: usize, const STEP: usize> Allocator for Bucketizer<A, MIN, MAX, STEP> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.bucket(layout.size()).ok_or(std::alloc::AllocError)?.allocate(layout)
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if let Some(bucket) = self.bucket(layout.size()) {
+            unsafe { bucket.deallocate(ptr, layout) }
+        }
+    }
+}
+
+impl<A: Owns, const MIN: usize, const MAX: usize, const STEP: usize> Owns for Bucketizer<A, MIN, MAX, STEP> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.bucket(layout.size()).is_some_and(|bucket| bucket.owns(ptr, layout))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Global, Layout};
+
+    use crate::compose::StatsAllocator;
+
+    use super::Bucketizer;
+    #[test]
+    fn bucketizer_test() {
+        let mut limits = Vec::new();
+        let allocator = Bucketizer::<_, 16, 100, 32>::new(|limit| {
+            limits.push(limit);
+            StatsAllocator::new(Global)
+        });
+        assert!(limits == [48, 80, 100], "Testing the bucket limits");
+        for size in [16, 48, 49, 100] {
+            let layout = Layout::from_size_align(size, 1).unwrap();
+            let allocation = allocator.allocate(layout).unwrap();
+            unsafe { allocator.deallocate(allocation.cast(), layout) };
+        }
+        let counts: Vec<_> = allocator.buckets().iter().map(|bucket| bucket.stats().allocations).collect();
+        assert!(counts == [2, 1, 1], "Testing sizes are routed to their bucket: {counts:?}");
+        assert!(allocator.allocate(Layout::new::<[u8; 101]>()).is_err() && allocator.allocate(Layout::new::<u64>()).is_err(), "Testing sizes outside of the range fail");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,76 @@
+use std::{alloc::{Allocator, Layout}, ptr::NonNull};
+
+use super::Owns;
+
+/// Allocates from `primary`, and from `secondary` whenever the primary fails.
+/// # Concepts
+/// The typical use is a fast fixed size allocator backed by a general purpose one,
+/// e.g. a [`crate::arena::PtrArena`] on the stack that spills over to [`std::alloc::Global`].
+/// Deallocations are routed by asking the primary whether it [`Owns`] the pointer.
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::{arena::PtrArena, compose::Fallback};
+///
+/// let mut scratch = [0u8; 256];
+/// let allocator = Fallback::new(unsafe { PtrArena::from_slice(&mut scratch) }, std::alloc::Global);
+/// let mut vector = Vec::new_in(&allocator);
+/// vector.extend(0..1024u32); // outgrows the scratch space
+/// ```
+pub struct Fallback<P, S> {
+    primary: P,
+    secondary: S,
+}
+
+impl<P, S> Fallback<P, S> {
+    pub fn new(primary: P, secondary: S) -> Self {
+        Self { primary, secondary }
+    }
+    pub fn primary(&self) -> &P {
+        &self.primary
+    }
+    pub fn secondary(&self) -> &S {
+        &self.secondary
+    }
+}
+
+unsafe impl<P: Allocator + Owns, S: Allocator> Allocator for Fallback<P, S> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.primary.allocate(layout).or_else(|_| self.secondary.allocate(layout))
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if self.primary.owns(ptr, layout) {
+            unsafe { self.primary.deallocate(ptr, layout) }
+        } else {
+            unsafe { self.secondary.deallocate(ptr, layout) }
+        }
+    }
+}
+
+impl<P: Owns, S: Owns> Owns for Fallback<P, S> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.primary.owns(ptr, layout) || self.secondary.owns(ptr, layout)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Global, Layout};
+
+    use crate::{arena::PtrArena, compose::{Owns, StatsAllocator}};
+
+    use super::Fallback;
+    #[test]
+    fn fallback_test() {
+        let mut scratch = [0u8; 64];
+        let allocator = Fallback::new(unsafe { PtrArena::from_slice(&mut scratch) }, StatsAllocator::new(Global));
+        let small = allocator.allocate(Layout::new::<[u8; 32]>()).unwrap();
+        let large = allocator.allocate(Layout::new::<[u8; 128]>()).unwrap();
+        assert!(allocator.primary().owns(small.cast(), Layout::new::<[u8; 32]>()), "Testing small allocations come from the primary");
+        assert!(allocator.secondary().stats().allocations == 1, "Testing the secondary is used once the primary is full");
+        unsafe {
+            allocator.deallocate(small.cast(), Layout::new::<[u8; 32]>());
+            allocator.deallocate(large.cast(), Layout::new::<[u8; 128]>());
+        }
+        assert!(allocator.secondary().stats().live_bytes == 0, "Testing deallocations are routed to the owner");
+    }
+}
\ No newline at end of file
@@ -0,0 +1,24 @@
+use std::{alloc::Layout, ptr::NonNull};
+
+mod fallback;
+mod segregator;
+mod bucketizer;
+mod affix;
+mod stats;
+pub use fallback::*;
+pub use segregator::*;
+pub use bucketizer::*;
+pub use affix::*;
+pub use stats::*;
+
+/// Allocators that can tell whether a pointer was handed out by them, [`Fallback`] uses
+/// it to send a deallocation to the right allocator.
+pub trait Owns {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool;
+}
+
+impl<T: Owns> Owns for &T {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        (**self).owns(ptr, layout)
+    }
+}
\ No newline at end of file
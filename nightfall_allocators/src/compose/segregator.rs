@@ -0,0 +1,112 @@
+use std::{alloc::{Allocator, Layout}, ptr::NonNull};
+
+use super::Owns;
+
+/// Sends allocations of at most `THRESHOLD` bytes to `Small` and everything else to `Large`.
+/// # Concepts
+/// Since every deallocation comes with the layout it was allocated with, routing is decided
+/// by the size alone and neither allocator needs to know about the other.
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::{arena::StandardArena, compose::Segregator};
+///
+/// // small nodes go to an arena, large buffers straight to the global allocator
+/// let allocator = Segregator::<256, _, _>::new(StandardArena::new(4096), std::alloc::Global);
+/// let boxed = Box::new_in([0u8; 64], &allocator);
+/// ```
+pub struct Segregator<const THRESHOLD: usize, Small, Large> {
+    small: Small,
+    large: Large,
+}
+
+impl<const THRESHOLD: usize, Small, Large> Segregator<THRESHOLD, Small, Large> {
+    pub fn new(small: Small, large: Large) -> Self {
+        Self { small, large }
+    }
+    pub fn small(&self) -> &Small {
+        &self.small
+    }
+    pub fn large(&self) -> &Large {
+        &self.large
+    }
+}
+
+unsafe impl<const THRESHOLD: usize, Small: Allocator, Large: Allocator> Allocator for Segregator<THRESHOLD, Small, Large> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if layout.size() <= THRESHOLD {
+            self.small.allocate(layout)
+        } else {
+            self.large.allocate(layout)
+        }
+    }
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if layout.size() <= THRESHOLD {
+            self.small.allocate_zeroed(layout)
+        } else {
+            self.large.allocate_zeroed(layout)
+        }
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        if layout.size() <= THRESHOLD {
+            unsafe { self.small.deallocate(ptr, layout) }
+        } else {
+            unsafe { self.large.deallocate(ptr, layout) }
+        }
+    }
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        match (old_layout.size() <= THRESHOLD, new_layout.size() <= THRESHOLD) {
+            (true, true) => unsafe { self.small.grow(ptr, old_layout, new_layout) },
+            (false, false) => unsafe { self.large.grow(ptr, old_layout, new_layout) },
+            _ => unsafe { self.move_to(ptr, old_layout, new_layout) },
+        }
+    }
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        match (old_layout.size() <= THRESHOLD, new_layout.size() <= THRESHOLD) {
+            (true, true) => unsafe { self.small.shrink(ptr, old_layout, new_layout) },
+            (false, false) => unsafe { self.large.shrink(ptr, old_layout, new_layout) },
+            _ => unsafe { self.move_to(ptr, old_layout, new_layout) },
+        }
+    }
+}
+
+impl<const THRESHOLD: usize, Small: Allocator, Large: Allocator> Segregator<THRESHOLD, Small, Large> {
+    /// moves an allocation that crosses the threshold to the other allocator.
+    unsafe fn move_to(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let new = self.allocate(new_layout)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr().cast::<u8>(), old_layout.size().min(new_layout.size()));
+            self.deallocate(ptr, old_layout);
+        }
+        Ok(new)
+    }
+}
+
+impl<const THRESHOLD: usize, Small: Owns, Large: Owns> Owns for Segregator<THRESHOLD, Small, Large> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        if layout.size() <= THRESHOLD {
+            self.small.owns(ptr, layout)
+        } else {
+            self.large.owns(ptr, layout)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::Global;
+
+    use crate::compose::StatsAllocator;
+
+    use super::Segregator;
+    #[test]
+    fn segregator_test() {
+        let allocator = Segregator::<64, _, _>::new(StatsAllocator::new(Global), StatsAllocator::new(Global));
+        let mut vector = Vec::<u8, _>::with_capacity_in(16, &allocator);
+        vector.extend(0..64);
+        assert!(allocator.small().stats().live_bytes == 64 && allocator.large().stats().allocations == 0, "Testing small allocations stay small");
+        vector.push(0);
+        assert!(allocator.small().stats().live_bytes == 0 && allocator.large().stats().live_bytes == vector.capacity(), "Testing growing past the threshold moves the allocation");
+        drop(vector);
+        assert!(allocator.large().stats().live_bytes == 0, "Testing the large allocation is freed by the large allocator");
+    }
+}
\ No newline at end of file
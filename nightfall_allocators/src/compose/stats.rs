@@ -0,0 +1,165 @@
+use std::{alloc::{Allocator, Layout}, ptr::NonNull, sync::atomic::{AtomicUsize, Ordering}};
+
+use crate::arena::Arena;
+
+use super::Owns;
+
+/// Snapshot of the counters of a [`StatsAllocator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AllocStats {
+    pub allocations: usize,
+    pub deallocations: usize,
+    /// allocations the inner allocator couldn't satisfy.
+    pub failures: usize,
+    pub grows: usize,
+    pub shrinks: usize,
+    /// bytes currently allocated, as requested by the callers.
+    pub live_bytes: usize,
+    /// highest amount of bytes that were live at once.
+    pub peak_bytes: usize,
+}
+
+/// Wrapper that counts the allocations made through it, see [`AllocStats`].
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::compose::StatsAllocator;
+///
+/// let allocator = StatsAllocator::new(std::alloc::Global);
+/// let mut vector = Vec::new_in(&allocator);
+/// vector.extend(0..100u8);
+/// assert!(allocator.stats().live_bytes == vector.capacity());
+/// ```
+pub struct StatsAllocator<A> {
+    inner: A,
+    allocations: AtomicUsize,
+    deallocations: AtomicUsize,
+    failures: AtomicUsize,
+    grows: AtomicUsize,
+    shrinks: AtomicUsize,
+    live: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl<A> StatsAllocator<A> {
+    pub fn new(inner: A) -> Self {
+        Self {
+            inner,
+            allocations: AtomicUsize::new(0),
+            deallocations: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            grows: AtomicUsize::new(0),
+            shrinks: AtomicUsize::new(0),
+            live: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    pub fn stats(&self) -> AllocStats {
+        AllocStats {
+            allocations: self.allocations.load(Ordering::Relaxed),
+            deallocations: self.deallocations.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            grows: self.grows.load(Ordering::Relaxed),
+            shrinks: self.shrinks.load(Ordering::Relaxed),
+            live_bytes: self.live.load(Ordering::Relaxed),
+            peak_bytes: self.peak.load(Ordering::Relaxed),
+        }
+    }
+    /// counts the outcome of an allocation that replaced `old` bytes with `new` bytes.
+    fn record(&self, result: Result<NonNull<[u8]>, std::alloc::AllocError>, old: usize, new: usize) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        match result {
+            Ok(_) if new >= old => {
+                let live = self.live.fetch_add(new - old, Ordering::Relaxed) + (new - old);
+                self.peak.fetch_max(live, Ordering::Relaxed);
+            }
+            Ok(_) => {
+                self.live.fetch_sub(old - new, Ordering::Relaxed);
+            }
+            Err(_) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for StatsAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.record(self.inner.allocate(layout), 0, layout.size())
+    }
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        self.record(self.inner.allocate_zeroed(layout), 0, layout.size())
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.deallocations.fetch_add(1, Ordering::Relaxed);
+        self.live.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { self.inner.deallocate(ptr, layout) }
+    }
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.grows.fetch_add(1, Ordering::Relaxed);
+        self.record(unsafe { self.inner.grow(ptr, old_layout, new_layout) }, old_layout.size(), new_layout.size())
+    }
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.grows.fetch_add(1, Ordering::Relaxed);
+        self.record(unsafe { self.inner.grow_zeroed(ptr, old_layout, new_layout) }, old_layout.size(), new_layout.size())
+    }
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.shrinks.fetch_add(1, Ordering::Relaxed);
+        self.record(unsafe { self.inner.shrink(ptr, old_layout, new_layout) }, old_layout.size(), new_layout.size())
+    }
+}
+
+impl<A: Owns> Owns for StatsAllocator<A> {
+    fn owns(&self, ptr: NonNull<u8>, layout: Layout) -> bool {
+        self.inner.owns(ptr, layout)
+    }
+}
+
+impl<A: Arena<Allocation = NonNull<[u8]>>> Arena for StatsAllocator<A> {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, crate::error::AllocError> {
+        self.allocations.fetch_add(1, Ordering::Relaxed);
+        let allocation = self.inner.arena_alloc(layout);
+        let _ = self.record(allocation.map_err(|_| std::alloc::AllocError), 0, layout.size());
+        allocation
+    }
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+    fn allocated(&self) -> usize {
+        self.inner.allocated()
+    }
+    unsafe fn clear(&self) {
+        self.live.store(0, Ordering::Relaxed);
+        unsafe { self.inner.clear() }
+    }
+    fn is_clear(&self) -> bool {
+        self.inner.is_clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::Global;
+
+    use super::StatsAllocator;
+    #[test]
+    fn stats_test() {
+        let allocator = StatsAllocator::new(Global);
+        let mut vector = Vec::<u8, _>::with_capacity_in(8, &allocator);
+        vector.extend(0..32);
+        vector.shrink_to_fit();
+        let stats = allocator.stats();
+        assert!(stats.allocations == 1 && stats.grows >= 1 && stats.shrinks == 0, "Testing the counters: {stats:?}");
+        vector.truncate(4);
+        vector.shrink_to_fit();
+        assert!(allocator.stats().live_bytes == 4 && allocator.stats().peak_bytes >= 32, "Testing live and peak bytes");
+        drop(vector);
+        let stats = allocator.stats();
+        assert!(stats.live_bytes == 0 && stats.deallocations == 1, "Testing deallocations are counted");
+    }
+}
\ No newline at end of file
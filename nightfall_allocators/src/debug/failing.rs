@@ -0,0 +1,188 @@
+use std::{alloc::{Allocator, Layout}, ptr::NonNull, sync::{atomic::{AtomicUsize, Ordering}, Mutex}};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Decides which allocations a [`FailingAllocator`] fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailurePolicy {
+    Never,
+    /// fails only the allocation with index `n`, counting from 0.
+    Nth(usize),
+    /// fails each allocation with the given probability. The same seed always fails
+    /// the same allocations. The probability is clamped to `0.0..=1.0`, and NaN never fails.
+    Random { probability: f64, seed: u64 },
+    /// fails any allocation that would bring the live bytes over the budget.
+    Budget(usize),
+}
+
+/// Debugging wrapper that fails allocations on purpose, to test out of memory paths.
+/// # Concepts
+/// Every failure is deterministic, so a test that walks through the allocations of an
+/// operation one by one can check that every single one of them is handled.
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::debug::FailingAllocator;
+///
+/// // pushing 16 values grows the vector three times
+/// for n in 0..3 {
+///     let mut vector = Vec::new_in(FailingAllocator::fail_nth(std::alloc::Global, n));
+///     for i in 0..16 {
+///         if vector.try_reserve(1).is_err() {
+///             break;
+///         }
+///         vector.push(i);
+///     }
+///     assert!(vector.allocator().failures() == 1);
+/// }
+/// ```
+pub struct FailingAllocator<A> {
+    inner: A,
+    policy: FailurePolicy,
+    rng: Mutex<StdRng>,
+    allocations: AtomicUsize,
+    failures: AtomicUsize,
+    live: AtomicUsize,
+}
+
+impl<A> FailingAllocator<A> {
+    pub fn new(inner: A, mut policy: FailurePolicy) -> Self {
+        let seed = match &mut policy {
+            FailurePolicy::Random { probability, seed } => {
+                *probability = if probability.is_nan() { 0.0 } else { probability.clamp(0.0, 1.0) };
+                *seed
+            }
+            _ => 0,
+        };
+        Self {
+            inner,
+            policy,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            allocations: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+            live: AtomicUsize::new(0),
+        }
+    }
+    /// fails only the allocation with index `n`, counting from 0.
+    pub fn fail_nth(inner: A, n: usize) -> Self {
+        Self::new(inner, FailurePolicy::Nth(n))
+    }
+    pub fn random(inner: A, probability: f64, seed: u64) -> Self {
+        Self::new(inner, FailurePolicy::Random { probability, seed })
+    }
+    pub fn budget(inner: A, bytes: usize) -> Self {
+        Self::new(inner, FailurePolicy::Budget(bytes))
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    pub fn policy(&self) -> FailurePolicy {
+        self.policy
+    }
+    /// amount of allocations attempted, including the failed ones.
+    pub fn allocations(&self) -> usize {
+        self.allocations.load(Ordering::Relaxed)
+    }
+    pub fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+    /// amount of bytes currently allocated through this allocator.
+    pub fn live_bytes(&self) -> usize {
+        self.live.load(Ordering::Relaxed)
+    }
+    /// decides whether an allocation of `size` bytes should fail, reserving the bytes if it doesn't.
+    fn should_fail(&self, size: usize) -> bool {
+        let index = self.allocations.fetch_add(1, Ordering::Relaxed);
+        let fail = match self.policy {
+            FailurePolicy::Never => false,
+            FailurePolicy::Nth(n) => index == n,
+            FailurePolicy::Random { probability, .. } => self.rng.lock().unwrap().random_bool(probability),
+            FailurePolicy::Budget(budget) => {
+                let reserved = self.live.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |live| {
+                    live.checked_add(size).filter(|&live| live <= budget)
+                });
+                return if reserved.is_err() {
+                    self.failures.fetch_add(1, Ordering::Relaxed);
+                    true
+                } else {
+                    false
+                };
+            }
+        };
+        if fail {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.live.fetch_add(size, Ordering::Relaxed);
+        }
+        fail
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for FailingAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if self.should_fail(layout.size()) {
+            return Err(std::alloc::AllocError);
+        }
+        self.inner.allocate(layout).inspect_err(|_| {
+            self.live.fetch_sub(layout.size(), Ordering::Relaxed);
+        })
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.live.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { self.inner.deallocate(ptr, layout) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Global, Layout};
+
+    use nightfall_collections::{error::CollectionError, rbtree::RBTree};
+
+    use crate::{arena::{Arena, StandardArena}, error::AllocError};
+
+    use super::FailingAllocator;
+    #[test]
+    fn nth_test() {
+        let failing = FailingAllocator::fail_nth(Global, 2);
+        let results: Vec<_> = (0..4).map(|_| failing.allocate(Layout::new::<u64>())).collect();
+        assert!(results.iter().map(|result| result.is_ok()).eq([true, true, false, true]), "Testing only the third allocation fails");
+        for result in results.into_iter().flatten() {
+            unsafe { failing.deallocate(result.cast(), Layout::new::<u64>()) };
+        }
+        assert!(failing.live_bytes() == 0 && failing.failures() == 1, "Testing the counters");
+    }
+    #[test]
+    fn random_test() {
+        let run = |seed| {
+            let failing = FailingAllocator::random(Global, 0.5, seed);
+            (0..64).map(|_| failing.allocate(Layout::new::<u8>()).map(|ptr| unsafe { failing.deallocate(ptr.cast(), Layout::new::<u8>()) }).is_ok()).collect::<Vec<_>>()
+        };
+        assert!(run(7) == run(7), "Testing the same seed fails the same allocations");
+        assert!(run(7).contains(&false) && run(7).contains(&true), "Testing some allocations fail");
+        for (probability, succeeds) in [(f64::NAN, true), (-1.0, true), (2.0, false), (f64::INFINITY, false)] {
+            let failing = FailingAllocator::random(Global, probability, 7);
+            assert!(failing.allocate(Layout::new::<u8>()).map(|ptr| unsafe { failing.deallocate(ptr.cast(), Layout::new::<u8>()) }).is_ok() == succeeds, "Testing out of range probabilities are clamped");
+        }
+        assert!(FailingAllocator::random(Global, f64::NAN, 7).policy() == super::FailurePolicy::Random { probability: 0.0, seed: 7 }, "Testing NaN never fails");
+    }
+    #[test]
+    fn budget_test() {
+        let arena = StandardArena::new_in(FailingAllocator::budget(Global, 1024*4), 1024);
+        arena.arena_alloc(Layout::new::<[u8; 1024]>()).unwrap();
+        let error = arena.arena_alloc(Layout::new::<[u8; 1024*8]>()).unwrap_err();
+        assert!(matches!(error, AllocError::OutOfMemory { requested: 8192, .. }), "Testing the arena reports out of memory");
+        let mut vector = Vec::<u8, _>::new_in(FailingAllocator::budget(Global, 64));
+        assert!(vector.try_reserve(64).is_ok() && vector.try_reserve(65).is_err(), "Testing the budget");
+    }
+    #[test]
+    fn rbtree_test() {
+        for n in 0..8 {
+            let mut tree = RBTree::new_in(FailingAllocator::fail_nth(Global, n));
+            let results: Vec<_> = (0..8).map(|i| tree.try_insert(i, i*2)).collect();
+            assert!(results[n] == Err(CollectionError::CapacityFull((n, n*2))), "Testing the failed insert hands the entry back");
+            assert!(results.iter().filter(|result| result.is_ok()).count() == 7, "Testing every other insert succeeds");
+            assert!(tree.len() == 7 && tree.get(&n).is_none(), "Testing the tree doesn't contain the failed entry");
+            assert!(tree.iter().map(|(k, _)| *k).eq((0..8).filter(|i| *i != n)), "Testing the tree is still in order");
+        }
+    }
+}
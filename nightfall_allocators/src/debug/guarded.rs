@@ -0,0 +1,193 @@
+use std::{alloc::{Allocator, Layout}, collections::HashMap, ptr::NonNull, sync::Mutex};
+
+use crate::{arena::Arena, error::AllocError};
+
+/// Number of guard bytes placed on each side of an allocation.
+pub const CANARY_SIZE: usize = 16;
+/// Pattern written into the guard bytes.
+pub const CANARY_BYTE: u8 = 0xFD;
+/// Pattern written into freshly allocated memory.
+pub const FRESH_BYTE: u8 = 0xCD;
+/// Pattern written into freed or cleared memory.
+pub const FREED_BYTE: u8 = 0xDD;
+
+/// Debugging wrapper that surrounds every allocation with guard bytes.
+/// # Concepts
+/// Each allocation is padded with [`CANARY_SIZE`] bytes of [`CANARY_BYTE`] on both sides.
+/// ```text
+/// ┌────────┬──────────────────────┬────────┐
+/// │ canary │       payload        │ canary │
+/// └────────┴──────────────────────┴────────┘
+/// ```
+/// The guard bytes are checked when the allocation is deallocated, when the wrapped
+/// [`Arena`] is cleared, or whenever [`GuardedAllocator::verify`] is called. Fresh memory
+/// is filled with [`FRESH_BYTE`] and freed or cleared memory with [`FREED_BYTE`], so reads
+/// of uninitialized or dangling memory stand out in a debugger.
+///
+/// Deallocating or clearing with corrupted guard bytes panics with the allocation's layout.
+pub struct GuardedAllocator<A> {
+    inner: A,
+    /// live allocations by payload address.
+    live: Mutex<HashMap<usize, Layout>>,
+}
+
+impl<A> GuardedAllocator<A> {
+    pub fn new(inner: A) -> Self {
+        Self { inner, live: Mutex::new(HashMap::new()) }
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    /// Checks the guard bytes of every live allocation.
+    pub fn verify(&self) -> Result<(), AllocError> {
+        for (&address, &layout) in self.live.lock().unwrap().iter() {
+            Self::check(address as *mut u8, layout)?;
+        }
+        Ok(())
+    }
+    /// amount of allocations that haven't been deallocated yet.
+    pub fn live_count(&self) -> usize {
+        self.live.lock().unwrap().len()
+    }
+    /// layout of the underlying allocation and offset of the payload inside of it.
+    fn padded_layout(layout: Layout) -> Result<(Layout, usize), AllocError> {
+        let front = CANARY_SIZE.max(layout.align());
+        let size = layout.size().checked_add(front + CANARY_SIZE).ok_or(AllocError::InvalidLayout)?;
+        Ok((Layout::from_size_align(size, layout.align())?, front))
+    }
+    /// writes the guard bytes around a fresh allocation and records it.
+    fn guard(&self, allocation: NonNull<[u8]>, layout: Layout, front: usize) -> NonNull<[u8]> {
+        unsafe {
+            let base = allocation.as_ptr().cast::<u8>();
+            let payload = base.add(front);
+            base.write_bytes(CANARY_BYTE, front);
+            payload.write_bytes(FRESH_BYTE, layout.size());
+            payload.add(layout.size()).write_bytes(CANARY_BYTE, CANARY_SIZE);
+            self.live.lock().unwrap().insert(payload as usize, layout);
+            NonNull::slice_from_raw_parts(NonNull::new_unchecked(payload), layout.size())
+        }
+    }
+    /// checks the guard bytes of an allocation, forgets it and poisons its memory.
+    /// Returns the start of the underlying allocation.
+    fn unguard(&self, ptr: NonNull<u8>, layout: Layout) -> (NonNull<u8>, Layout) {
+        let recorded = self.live.lock().unwrap().remove(&(ptr.as_ptr() as usize));
+        match recorded {
+            Some(recorded) => assert!(recorded == layout, "allocation at {ptr:p} was allocated with {recorded:?} but deallocated with {layout:?}"),
+            None => panic!("deallocated {ptr:p} ({layout:?}) which isn't a live allocation"),
+        }
+        if let Err(error) = Self::check(ptr.as_ptr(), layout) {
+            panic!("{error}");
+        }
+        let (padded, front) = Self::padded_layout(layout).unwrap();
+        unsafe {
+            let base = ptr.as_ptr().sub(front);
+            base.write_bytes(FREED_BYTE, padded.size());
+            (NonNull::new_unchecked(base), padded)
+        }
+    }
+    fn check(payload: *mut u8, layout: Layout) -> Result<(), AllocError> {
+        let front = CANARY_SIZE.max(layout.align());
+        let (front, back) = unsafe {
+            (std::slice::from_raw_parts(payload.sub(front), front), std::slice::from_raw_parts(payload.add(layout.size()), CANARY_SIZE))
+        };
+        if front.iter().chain(back).all(|&byte| byte == CANARY_BYTE) {
+            Ok(())
+        } else {
+            Err(AllocError::GuardCorrupted { address: payload as usize, layout })
+        }
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for GuardedAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let (padded, front) = Self::padded_layout(layout).map_err(|_|std::alloc::AllocError)?;
+        let allocation = self.inner.allocate(padded)?;
+        Ok(self.guard(allocation, layout, front))
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        let (base, padded) = self.unguard(ptr, layout);
+        unsafe { self.inner.deallocate(base, padded) }
+    }
+}
+
+impl<A: Arena<Allocation = NonNull<[u8]>>> Arena for GuardedAllocator<A> {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        let (padded, front) = Self::padded_layout(layout)?;
+        let allocation = self.inner.arena_alloc(padded)?;
+        Ok(self.guard(allocation, layout, front))
+    }
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+    fn allocated(&self) -> usize {
+        self.inner.allocated()
+    }
+    /// Checks the guard bytes of every allocation and poisons them before clearing the arena.
+    unsafe fn clear(&self) {
+        if let Err(error) = self.verify() {
+            panic!("{error}");
+        }
+        for (address, layout) in self.live.lock().unwrap().drain() {
+            let (padded, front) = Self::padded_layout(layout).unwrap();
+            unsafe { (address as *mut u8).sub(front).write_bytes(FREED_BYTE, padded.size()) };
+        }
+        unsafe { self.inner.clear() }
+    }
+    fn is_clear(&self) -> bool {
+        self.inner.is_clear()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Layout};
+
+    use crate::arena::{Arena, StandardArena};
+
+    use super::{GuardedAllocator, FREED_BYTE, FRESH_BYTE};
+    #[test]
+    fn guard_test() {
+        let guarded = GuardedAllocator::new(StandardArena::new(1024));
+        let layout = Layout::new::<[u8; 32]>();
+        let allocation = guarded.arena_alloc(layout).unwrap();
+        let bytes = unsafe { allocation.as_ref() };
+        assert!(bytes.len() == 32 && bytes.iter().all(|&byte| byte == FRESH_BYTE), "Testing fresh memory is filled");
+        guarded.verify().unwrap();
+        // write one byte past the end of the allocation
+        unsafe { allocation.cast::<u8>().add(32).write(0) };
+        let error = guarded.verify().unwrap_err().to_string();
+        assert!(error.contains("size: 32"), "Testing corruption is reported with the layout: {error}");
+        let clear = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| unsafe { guarded.clear() }));
+        assert!(clear.is_err(), "Testing clearing a corrupted arena panics");
+    }
+    #[test]
+    fn poison_test() {
+        let guarded = GuardedAllocator::new(StandardArena::new(1024));
+        let layout = Layout::new::<u64>();
+        let allocation = guarded.allocate(layout).unwrap();
+        let ptr = allocation.cast::<u8>();
+        unsafe { ptr.cast::<u64>().write(42) };
+        unsafe { guarded.deallocate(ptr, layout) };
+        assert!(unsafe { ptr.cast::<[u8; 8]>().read() } == [FREED_BYTE; 8], "Testing freed memory is poisoned");
+        assert!(guarded.live_count() == 0, "Testing the allocation was forgotten");
+        let allocation = guarded.arena_alloc(layout).unwrap().cast::<u8>();
+        unsafe { guarded.clear() };
+        assert!(unsafe { allocation.cast::<[u8; 8]>().read() } == [FREED_BYTE; 8], "Testing cleared memory is poisoned");
+        assert!(guarded.is_clear(), "Testing the wrapped arena was cleared");
+        let mut vector = Vec::new_in(&guarded);
+        vector.extend(0..100u32);
+        assert!(vector.iter().sum::<u32>() == 99*100/2, "Testing vectors grow correctly");
+    }
+    #[test]
+    #[should_panic(expected = "which isn't a live allocation")]
+    fn double_free_test() {
+        let guarded = GuardedAllocator::new(std::alloc::Global);
+        let layout = Layout::new::<u64>();
+        let allocation = guarded.allocate(layout).unwrap().cast::<u8>();
+        unsafe {
+            guarded.deallocate(allocation, layout);
+            guarded.deallocate(allocation, layout);
+        }
+    }
+}
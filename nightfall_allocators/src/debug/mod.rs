@@ -0,0 +1,6 @@
+mod guarded;
+mod tracking;
+mod failing;
+pub use guarded::*;
+pub use tracking::*;
+pub use failing::*;
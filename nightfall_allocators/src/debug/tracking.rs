@@ -0,0 +1,194 @@
+use std::{alloc::{Allocator, Layout}, backtrace::Backtrace, collections::HashMap, fmt::Display, ptr::NonNull, sync::{Arc, Mutex}, thread::ThreadId};
+
+use crate::{arena::Arena, error::AllocError};
+
+/// What a [`TrackingAllocator`] does when it's dropped while allocations are still live.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeakPolicy {
+    /// panics, listing every leaked allocation. If the thread is already panicking
+    /// the leaks are reported instead.
+    #[default]
+    Panic,
+    /// prints every leaked allocation to stderr.
+    Report,
+    Ignore,
+}
+
+/// A live allocation recorded by a [`TrackingAllocator`].
+#[derive(Debug, Clone)]
+pub struct AllocationRecord {
+    pub address: usize,
+    pub layout: Layout,
+    /// thread the allocation was made on.
+    pub thread: ThreadId,
+    /// only captured when the allocator was created with [`TrackingAllocator::with_backtraces`].
+    pub backtrace: Option<Arc<Backtrace>>,
+}
+
+impl Display for AllocationRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:#x} ({:?}) allocated on {:?}", self.address, self.layout, self.thread)?;
+        if let Some(backtrace) = &self.backtrace {
+            write!(f, "\n{backtrace}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Debugging wrapper that records every live allocation made through it.
+/// # Concepts
+/// Every allocation is recorded with its layout, address and the thread it was made on,
+/// and optionally the backtrace of the allocation site. Deallocating forgets the record
+/// and clearing the wrapped [`Arena`] forgets all of them. Whatever is still recorded when
+/// the allocator is dropped is a leak, and is handled according to its [`LeakPolicy`].
+/// ```
+/// # #![feature(allocator_api)]
+/// use nightfall_allocators::{arena::StandardArena, debug::TrackingAllocator};
+///
+/// let arena = StandardArena::new_in(TrackingAllocator::new(std::alloc::Global), 1024);
+/// ```
+pub struct TrackingAllocator<A> {
+    inner: A,
+    live: Mutex<HashMap<usize, AllocationRecord>>,
+    backtraces: bool,
+    policy: LeakPolicy,
+}
+
+impl<A> TrackingAllocator<A> {
+    pub fn new(inner: A) -> Self {
+        Self { inner, live: Mutex::new(HashMap::new()), backtraces: false, policy: LeakPolicy::default() }
+    }
+    /// captures a backtrace for every allocation, this is slow.
+    pub fn with_backtraces(mut self) -> Self {
+        self.backtraces = true;
+        self
+    }
+    pub fn with_leak_policy(mut self, policy: LeakPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    /// Returns every allocation that hasn't been deallocated yet, sorted by address.
+    pub fn live_allocations(&self) -> Vec<AllocationRecord> {
+        let mut live: Vec<_> = self.live.lock().unwrap().values().cloned().collect();
+        live.sort_by_key(|record| record.address);
+        live
+    }
+    /// amount of bytes currently allocated through this allocator.
+    pub fn live_bytes(&self) -> usize {
+        self.live.lock().unwrap().values().map(|record| record.layout.size()).sum()
+    }
+    fn record(&self, allocation: NonNull<[u8]>, layout: Layout) -> NonNull<[u8]> {
+        let backtrace = self.backtraces.then(|| Arc::new(Backtrace::force_capture()));
+        let address = allocation.as_ptr().cast::<u8>() as usize;
+        let record = AllocationRecord { address, layout, thread: std::thread::current().id(), backtrace };
+        self.live.lock().unwrap().insert(address, record);
+        allocation
+    }
+    fn forget(&self, ptr: NonNull<u8>) {
+        self.live.lock().unwrap().remove(&(ptr.as_ptr() as usize));
+    }
+}
+
+unsafe impl<A: Allocator> Allocator for TrackingAllocator<A> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        Ok(self.record(self.inner.allocate(layout)?, layout))
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.forget(ptr);
+        unsafe { self.inner.deallocate(ptr, layout) }
+    }
+}
+
+impl<A: Arena<Allocation = NonNull<[u8]>>> Arena for TrackingAllocator<A> {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        Ok(self.record(self.inner.arena_alloc(layout)?, layout))
+    }
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+    fn allocated(&self) -> usize {
+        self.inner.allocated()
+    }
+    unsafe fn clear(&self) {
+        self.live.lock().unwrap().clear();
+        unsafe { self.inner.clear() }
+    }
+    fn is_clear(&self) -> bool {
+        self.inner.is_clear()
+    }
+}
+
+impl<A> Drop for TrackingAllocator<A> {
+    fn drop(&mut self) {
+        let leaks = self.live_allocations();
+        if leaks.is_empty() || self.policy == LeakPolicy::Ignore {
+            return;
+        }
+        let mut report = format!("{} allocation(s) leaked:", leaks.len());
+        for leak in &leaks {
+            report.push_str(&format!("\n{leak}"));
+        }
+        if self.policy == LeakPolicy::Panic && !std::thread::panicking() {
+            panic!("{report}");
+        } else {
+            eprintln!("{report}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::{Allocator, Global, Layout}, ptr::NonNull};
+
+    use crate::{arena::{Arena, StandardArena}, pool::{Pool, PoolAllocator}};
+
+    use super::{LeakPolicy, TrackingAllocator};
+    #[test]
+    fn tracking_test() {
+        let tracking = TrackingAllocator::new(Global).with_backtraces();
+        let layout = Layout::new::<[u32; 8]>();
+        let a = tracking.allocate(layout).unwrap();
+        let b = tracking.allocate(Layout::new::<u8>()).unwrap();
+        let live = tracking.live_allocations();
+        assert!(live.len() == 2 && tracking.live_bytes() == 33, "Testing both allocations are live");
+        let record = live.iter().find(|record| record.address == a.cast::<u8>().as_ptr() as usize).unwrap();
+        assert!(record.layout == layout && record.thread == std::thread::current().id(), "Testing the record is correct");
+        assert!(record.backtrace.is_some(), "Testing the backtrace was captured");
+        unsafe {
+            tracking.deallocate(a.cast(), layout);
+            tracking.deallocate(b.cast(), Layout::new::<u8>());
+        }
+        assert!(tracking.live_allocations().is_empty(), "Testing deallocations are forgotten");
+    }
+    #[test]
+    fn backing_test() {
+        // dropping the arena gives every chunk back, so nothing leaks
+        let arena = StandardArena::new_in(TrackingAllocator::new(Global), 1024);
+        arena.arena_alloc(Layout::new::<[u8; 4096]>()).unwrap();
+        drop(arena);
+        let tracking = TrackingAllocator::new(StandardArena::new(1024*4));
+        let pool = Pool::<u64>::from_arena(&tracking, Layout::new::<[u64; 16]>()).unwrap();
+        let value = pool.allocate().unwrap();
+        pool.deallocate(value).unwrap();
+        assert!(tracking.live_allocations().len() == 1, "Testing the pool's memory is tracked");
+        unsafe { tracking.clear() };
+        assert!(tracking.live_allocations().is_empty(), "Testing clearing forgets every allocation");
+    }
+    #[test]
+    #[should_panic]
+    fn leak_test() {
+        let tracking = TrackingAllocator::new(Global);
+        let _ = tracking.allocate(Layout::new::<u64>()).unwrap();
+    }
+    #[test]
+    fn report_test() {
+        let tracking = TrackingAllocator::new(Global).with_leak_policy(LeakPolicy::Report);
+        let leak = tracking.allocate(Layout::new::<u64>()).unwrap();
+        drop(tracking);
+        unsafe { Global.deallocate(NonNull::new(leak.as_ptr().cast()).unwrap(), Layout::new::<u64>()) };
+    }
+}
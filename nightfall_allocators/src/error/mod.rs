@@ -1,7 +1,54 @@
+use std::alloc::{Layout, LayoutError};
+
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
 pub enum AllocError {
-    #[error("Out of Memory")]
-    OutOfMemory
+    /// `available` is how many bytes the allocator had left, which may still be too
+    /// fragmented or misaligned to hold the request.
+    #[error("Out of Memory, requested {requested} bytes but only {available} are available")]
+    OutOfMemory { requested: usize, available: usize },
+    #[error("Invalid layout")]
+    InvalidLayout,
+    #[error("Pointer {address:#x} doesn't belong to this allocator")]
+    ForeignPointer { address: usize },
+    #[error("Pointer {address:#x} was freed twice")]
+    DoubleFree { address: usize },
+    #[error("Heap corrupted at {address:#x}: {reason}")]
+    Corrupted { address: usize, reason: &'static str },
+    #[error("Guard bytes of the allocation at {address:#x} ({layout:?}) were overwritten")]
+    GuardCorrupted { address: usize, layout: Layout },
+}
+
+impl From<LayoutError> for AllocError {
+    fn from(_: LayoutError) -> Self {
+        AllocError::InvalidLayout
+    }
+}
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    #[error("Invalid snapshot: {0}")]
+    Invalid(&'static str),
+    #[error("Chunk {chunk} of the snapshot holds {expected} bytes but the arena's holds {found}")]
+    Incompatible { chunk: usize, expected: usize, found: usize },
+    #[error(transparent)]
+    Alloc(#[from] AllocError),
+}
+
+#[derive(Debug, Error)]
+pub enum TraceError {
+    #[error("Invalid trace event on line {line}: {reason}")]
+    InvalidEvent { line: usize, reason: &'static str },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+
+#[derive(Debug, Error)]
+pub enum MappedError {
+    #[error("Invalid arena file: {0}")]
+    InvalidFile(&'static str),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
 }
\ No newline at end of file
@@ -0,0 +1,176 @@
+use std::{alloc::{Allocator, GlobalAlloc, Layout}, cell::{Cell, UnsafeCell}, ptr::NonNull, sync::{atomic::{AtomicUsize, Ordering}, OnceLock}};
+
+thread_local! {
+    // const initialized without a destructor, so accessing it never allocates
+    static IN_ALLOCATOR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Resets the re-entrancy flag of the current thread when dropped.
+struct ReentrancyGuard;
+
+impl ReentrancyGuard {
+    /// returns `None` if the current thread is already inside the allocator.
+    fn enter() -> Option<Self> {
+        // if the thread local is gone the thread is being torn down, treat it as re-entrant
+        let entered = IN_ALLOCATOR.try_with(|flag| !flag.replace(true)).unwrap_or(false);
+        // not `then_some`, constructing the guard eagerly would drop it and reset the flag
+        entered.then(|| Self)
+    }
+}
+
+impl Drop for ReentrancyGuard {
+    fn drop(&mut self) {
+        let _ = IN_ALLOCATOR.try_with(|flag| flag.set(false));
+    }
+}
+
+/// Adapter that lets a nightfall allocator be installed with `#[global_allocator]`.
+/// # Concepts
+/// The wrapped allocator is created lazily on the first allocation. Creating it, or
+/// using it for the first time on a new thread (like [`crate::arena::AsyncArena`] does
+/// through `ThreadLocal`), can itself allocate, which would call back into the global
+/// allocator. Any allocation made while the current thread is already inside the
+/// allocator is served from a small static bootstrap region of `BOOTSTRAP` bytes instead,
+/// and freeing memory from that region is a no-op.
+/// ```ignore
+/// use std::alloc::System;
+/// use nightfall_allocators::{arena::AsyncArena, global::NightfallGlobal};
+///
+/// #[global_allocator]
+/// static GLOBAL: NightfallGlobal<AsyncArena<System>> = NightfallGlobal::new(|| AsyncArena::new_in(System, 1024*1024));
+/// ```
+/// The wrapped allocator should get its own memory from [`std::alloc::System`], not
+/// [`std::alloc::Global`], otherwise every chunk it requests goes through the bootstrap region.
+pub struct NightfallGlobal<A: Allocator + Sync, const BOOTSTRAP: usize = 65536> {
+    allocator: OnceLock<A>,
+    init: fn() -> A,
+    bootstrap: UnsafeCell<[u8; BOOTSTRAP]>,
+    bootstrap_offset: AtomicUsize,
+}
+
+unsafe impl<A: Allocator + Sync, const BOOTSTRAP: usize> Sync for NightfallGlobal<A, BOOTSTRAP> {}
+
+impl<A: Allocator + Sync, const BOOTSTRAP: usize> NightfallGlobal<A, BOOTSTRAP> {
+    pub const fn new(init: fn() -> A) -> Self {
+        Self { allocator: OnceLock::new(), init, bootstrap: UnsafeCell::new([0; BOOTSTRAP]), bootstrap_offset: AtomicUsize::new(0) }
+    }
+    /// returns the wrapped allocator, creating it if it doesn't exist yet.
+    pub fn allocator(&self) -> &A {
+        self.allocator.get_or_init(self.init)
+    }
+    /// amount of bytes handed out from the bootstrap region.
+    pub fn bootstrap_allocated(&self) -> usize {
+        self.bootstrap_offset.load(Ordering::Relaxed)
+    }
+    fn bootstrap_alloc(&self, layout: Layout) -> *mut u8 {
+        let base = self.bootstrap.get().cast::<u8>();
+        let mut offset = self.bootstrap_offset.load(Ordering::Relaxed);
+        loop {
+            let address = base as usize + offset;
+            let start = offset + (address.next_multiple_of(layout.align()) - address);
+            let end = match start.checked_add(layout.size()) {
+                Some(end) if end <= BOOTSTRAP => end,
+                _ => return std::ptr::null_mut(),
+            };
+            match self.bootstrap_offset.compare_exchange_weak(offset, end, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return unsafe { base.add(start) },
+                Err(current) => offset = current,
+            }
+        }
+    }
+    fn is_bootstrap(&self, ptr: *mut u8) -> bool {
+        let base = self.bootstrap.get().cast::<u8>() as usize;
+        (base..base + BOOTSTRAP).contains(&(ptr as usize))
+    }
+}
+
+unsafe impl<A: Allocator + Sync, const BOOTSTRAP: usize> GlobalAlloc for NightfallGlobal<A, BOOTSTRAP> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match ReentrancyGuard::enter() {
+            Some(_guard) => self.allocator().allocate(layout).map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr().cast()),
+            None => self.bootstrap_alloc(layout),
+        }
+    }
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        match ReentrancyGuard::enter() {
+            Some(_guard) => self.allocator().allocate_zeroed(layout).map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr().cast()),
+            // the bootstrap region is never reused, so it's still zeroed
+            None => self.bootstrap_alloc(layout),
+        }
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        if self.is_bootstrap(ptr) {
+            return;
+        }
+        // anything outside of the bootstrap region came from the wrapped allocator, so it
+        // already exists, even if this is a re-entrant call
+        let _guard = ReentrancyGuard::enter();
+        unsafe { self.allocator().deallocate(NonNull::new_unchecked(ptr), layout) };
+    }
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_layout = unsafe { Layout::from_size_align_unchecked(new_size, layout.align()) };
+        if !self.is_bootstrap(ptr) && let Some(_guard) = ReentrancyGuard::enter() {
+            let ptr = unsafe { NonNull::new_unchecked(ptr) };
+            let result = if new_size >= layout.size() {
+                unsafe { self.allocator().grow(ptr, layout, new_layout) }
+            } else {
+                unsafe { self.allocator().shrink(ptr, layout, new_layout) }
+            };
+            return result.map_or(std::ptr::null_mut(), |ptr| ptr.as_ptr().cast());
+        }
+        let new = unsafe { self.alloc(new_layout) };
+        if !new.is_null() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(ptr, new, layout.size().min(new_size));
+                self.dealloc(ptr, layout);
+            }
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{GlobalAlloc, Layout, System};
+
+    use crate::arena::AsyncArena;
+
+    use super::{NightfallGlobal, ReentrancyGuard};
+
+    static ARENA: NightfallGlobal<AsyncArena<System>, 4096> = NightfallGlobal::new(|| AsyncArena::new_in(System, 1024*64));
+    #[test]
+    fn global_test() {
+        let layout = Layout::new::<[u64; 16]>();
+        let ptr = unsafe { ARENA.alloc(layout) };
+        assert!(!ptr.is_null() && !ARENA.is_bootstrap(ptr), "Testing allocation came from the arena");
+        let threads: Vec<_> = (0..4).map(|_| std::thread::spawn(move || {
+            let ptr = unsafe { ARENA.alloc(layout) };
+            assert!(!ptr.is_null(), "Testing allocation on another thread");
+            ptr as usize
+        })).collect();
+        for thread in threads {
+            assert!(thread.join().unwrap() != ptr as usize, "Testing threads get their own arena");
+        }
+        let grown = unsafe { ARENA.realloc(ptr, layout, 1024) };
+        assert!(!grown.is_null(), "Testing reallocation");
+        unsafe { ARENA.dealloc(grown, Layout::from_size_align(1024, layout.align()).unwrap()) };
+    }
+    #[test]
+    fn reentrancy_test() {
+        static HEAP: NightfallGlobal<AsyncArena<System>, 256> = NightfallGlobal::new(|| AsyncArena::new_in(System, 1024*16));
+        let layout = Layout::new::<u128>();
+        let guard = ReentrancyGuard::enter();
+        assert!(guard.is_some() && ReentrancyGuard::enter().is_none(), "Testing the guard detects re-entrancy");
+        let bootstrap = unsafe { HEAP.alloc(layout) };
+        assert!(HEAP.is_bootstrap(bootstrap), "Testing re-entrant allocations use the bootstrap region");
+        assert!(HEAP.bootstrap_allocated() >= layout.size(), "Testing bootstrap region is bump allocated");
+        assert!(unsafe { HEAP.alloc(Layout::new::<[u8; 512]>()) }.is_null(), "Testing bootstrap exhaustion fails the allocation");
+        drop(guard);
+        let ptr = unsafe { HEAP.alloc(layout) };
+        assert!(!HEAP.is_bootstrap(ptr), "Testing allocation came from the arena");
+        unsafe {
+            HEAP.dealloc(bootstrap, layout);
+            HEAP.dealloc(ptr, layout);
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{alloc::{Allocator, Layout}, cell::{Cell, UnsafeCell}, ptr::NonNull};
+
+use nightfall_collections::bitmap::BitSet;
+
+use crate::{arena::Arena, compose::Owns, error::AllocError};
+
+/// Allocator handing out runs of fixed size blocks, with one occupancy bit per block.
+/// # Concepts
+/// Free blocks are found by scanning the bitmap a word at a time, skipping full words
+/// without looking at their bits and using `trailing_zeros` inside a word. An allocation
+/// takes as many contiguous blocks as it needs, so any size fits as long as a free run is
+/// long enough. Freeing clears the bits of the run, and [`Arena::clear`] frees every block
+/// at once by zeroing the bitmap.
+/// ```text
+/// bitmap  1 1 1 0 0 1 0 0 0 0
+/// blocks ┌─┬─┬─┬─┬─┬─┬─┬─┬─┬─┐
+///        │a│a│b│ │ │c│ │ │ │ │
+///        └─┴─┴─┴─┴─┴─┴─┴─┴─┴─┘
+/// ```
+pub struct BitmapBlockAllocator {
+    ptr: *mut u8,
+    block_size: usize,
+    blocks: UnsafeCell<BitSet>,
+    used: Cell<usize>,
+}
+
+impl BitmapBlockAllocator {
+    /// # Safety
+    /// `ptr` must be valid for reads and writes of `size` bytes for as long as the allocator is used.
+    /// Any bytes after the last whole block are left unused.
+    pub unsafe fn from_raw(ptr: *mut u8, size: usize, block_size: usize) -> Self {
+        assert!(block_size > 0, "block size must not be zero");
+        Self { ptr, block_size, blocks: UnsafeCell::new(BitSet::new(size / block_size)), used: Cell::new(0) }
+    }
+    /// # Safety
+    /// `slice` must outlive the allocator and not be accessed while the allocator is used.
+    pub unsafe fn from_slice(slice: &mut [u8], block_size: usize) -> Self {
+        unsafe { Self::from_raw(slice.as_mut_ptr(), slice.len(), block_size) }
+    }
+    /// takes `count` blocks from `arena`, aligned to the block size if it's a power of two.
+    pub fn from_arena(arena: &dyn Arena<Allocation = NonNull<[u8]>>, count: usize, block_size: usize) -> Result<Self, AllocError> {
+        let align = if block_size.is_power_of_two() { block_size } else { 1 };
+        let size = count.checked_mul(block_size).ok_or(AllocError::InvalidLayout)?;
+        let ptr = arena.arena_alloc(Layout::from_size_align(size, align)?)?;
+        Ok(unsafe { Self::from_raw(ptr.as_ptr().cast(), size, block_size) })
+    }
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+    pub fn block_count(&self) -> usize {
+        self.bitmap().len()
+    }
+    pub fn used_blocks(&self) -> usize {
+        self.used.get()
+    }
+    pub fn free_blocks(&self) -> usize {
+        self.block_count() - self.used.get()
+    }
+    /// share of the blocks that are in use, between 0 and 1.
+    pub fn occupancy(&self) -> f64 {
+        if self.block_count() == 0 {
+            0.0
+        } else {
+            self.used.get() as f64 / self.block_count() as f64
+        }
+    }
+    /// length in blocks of the longest free run, the largest allocation that can still succeed.
+    pub fn largest_free_run(&self) -> usize {
+        let bitmap = self.bitmap();
+        let mut largest = 0;
+        let mut start = 0;
+        while let Some(free) = bitmap.first_zero(start) {
+            let end = bitmap.first_one(free, bitmap.len()).unwrap_or(bitmap.len());
+            largest = largest.max(end - free);
+            start = end;
+        }
+        largest
+    }
+    /// whether the block at `index` is in use.
+    pub fn is_used(&self, index: usize) -> bool {
+        self.bitmap().active(index)
+    }
+    /// Allocates enough contiguous blocks to hold `layout`. Fails with [`AllocError::InvalidLayout`]
+    /// if no block starts at an address aligned to `layout`.
+    pub fn malloc(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let count = layout.size().div_ceil(self.block_size).max(1);
+        let (first, stride) = self.aligned_blocks(layout.align()).ok_or(AllocError::InvalidLayout)?;
+        let Some(start) = self.find_run(count, first, stride) else {
+            Err(AllocError::OutOfMemory { requested: layout.size(), available: self.free_blocks()*self.block_size })?
+        };
+        self.bitmap().flip_range(start, start + count, true);
+        self.used.set(self.used.get() + count);
+        Ok(NonNull::slice_from_raw_parts(NonNull::new(self.block_ptr(start)).unwrap(), count*self.block_size))
+    }
+    /// Frees the blocks of an allocation.
+    /// # Safety
+    /// `ptr` must have been returned by [`BitmapBlockAllocator::malloc`] on this allocator with a
+    /// layout of `size` bytes, and not freed yet.
+    pub unsafe fn free(&self, ptr: NonNull<u8>, size: usize) {
+        let start = (ptr.as_ptr() as usize - self.ptr as usize) / self.block_size;
+        let count = size.div_ceil(self.block_size).max(1);
+        debug_assert!(self.bitmap().first_zero(start).is_none_or(|free| free >= start + count), "blocks freed twice");
+        self.bitmap().flip_range(start, start + count, false);
+        self.used.set(self.used.get() - count);
+    }
+    /// index of the first block aligned to `align` and the distance in blocks to the next aligned ones,
+    /// `None` if no block is aligned.
+    fn aligned_blocks(&self, align: usize) -> Option<(usize, usize)> {
+        // the alignment of the blocks repeats every `align / gcd(block_size, align)` blocks
+        let stride = align >> self.block_size.trailing_zeros().min(align.trailing_zeros());
+        let first = (0..stride.min(self.block_count())).find(|&index| (self.block_ptr(index) as usize).is_multiple_of(align))?;
+        Some((first, stride))
+    }
+    /// first run of `count` free blocks starting at one of the aligned blocks `first + n*stride`.
+    fn find_run(&self, count: usize, first: usize, stride: usize) -> Option<usize> {
+        let bitmap = self.bitmap();
+        let mut start = 0;
+        loop {
+            start = bitmap.first_zero(start)?;
+            start = first + start.saturating_sub(first).div_ceil(stride)*stride;
+            let end = start.checked_add(count).filter(|&end| end <= bitmap.len())?;
+            match bitmap.first_one(start, end) {
+                Some(used) => start = used + 1,
+                None => return Some(start),
+            }
+        }
+    }
+    fn block_ptr(&self, index: usize) -> *mut u8 {
+        self.ptr.wrapping_add(index*self.block_size)
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn bitmap(&self) -> &mut BitSet {
+        unsafe { self.blocks.get().as_mut().unwrap() }
+    }
+}
+
+unsafe impl Allocator for BitmapBlockAllocator {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.malloc(layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        unsafe { self.free(ptr, layout.size()) }
+    }
+}
+
+impl Arena for BitmapBlockAllocator {
+    type Allocation = NonNull<[u8]>;
+    fn arena_alloc(&self, layout: Layout) -> Result<Self::Allocation, AllocError> {
+        self.malloc(layout)
+    }
+    fn size(&self) -> usize {
+        self.block_count()*self.block_size
+    }
+    fn allocated(&self) -> usize {
+        self.used.get()*self.block_size
+    }
+    unsafe fn clear(&self) {
+        self.bitmap().clear();
+        self.used.set(0);
+    }
+    fn is_clear(&self) -> bool {
+        self.used.get() == 0
+    }
+}
+
+impl Owns for BitmapBlockAllocator {
+    fn owns(&self, ptr: NonNull<u8>, _: Layout) -> bool {
+        (self.ptr as usize..self.block_ptr(self.block_count()) as usize).contains(&(ptr.as_ptr() as usize))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Layout};
+
+    use crate::{arena::{Arena, StandardArena}, error::AllocError};
+
+    use super::BitmapBlockAllocator;
+    #[test]
+    fn block_test() {
+        let mut memory = vec![0u8; 64*100];
+        let blocks = unsafe { BitmapBlockAllocator::from_slice(&mut memory, 64) };
+        let a = blocks.malloc(Layout::new::<[u8; 64]>()).unwrap();
+        let b = blocks.malloc(Layout::new::<[u8; 65]>()).unwrap();
+        assert!(a.len() == 64 && b.len() == 128 && blocks.used_blocks() == 3, "Testing allocations take whole blocks");
+        unsafe { blocks.free(a.cast(), 64) };
+        // the freed block is too small for two blocks, so the run starts after b
+        let c = blocks.malloc(Layout::new::<[u8; 128]>()).unwrap();
+        assert!(c.cast::<u8>().as_ptr() as usize == b.cast::<u8>().as_ptr() as usize + 128, "Testing runs skip holes that are too small");
+        let d = blocks.malloc(Layout::new::<u8>()).unwrap();
+        assert!(d.cast::<u8>() == a.cast::<u8>(), "Testing single blocks fill holes");
+        assert!(blocks.largest_free_run() == 95 && blocks.free_blocks() == 95, "Testing occupancy reporting");
+        assert!(blocks.malloc(Layout::new::<[u8; 64*96]>()).is_err(), "Testing a run that doesn't fit fails");
+        unsafe { blocks.clear() };
+        assert!(blocks.is_clear() && blocks.largest_free_run() == 100, "Testing clearing frees every block");
+    }
+    #[test]
+    fn run_test() {
+        // runs crossing word boundaries of the bitmap
+        let arena = StandardArena::new(1024*64);
+        let blocks = BitmapBlockAllocator::from_arena(&arena, 200, 16).unwrap();
+        let first = blocks.allocate(Layout::new::<[u8; 16*60]>()).unwrap();
+        let second = blocks.allocate(Layout::new::<[u8; 16*100]>()).unwrap();
+        assert!(blocks.used_blocks() == 160 && (0..160).all(|index| blocks.is_used(index)), "Testing runs spanning words are marked");
+        unsafe { blocks.deallocate(first.cast(), Layout::new::<[u8; 16*60]>()) };
+        let aligned = blocks.allocate(Layout::from_size_align(16, 256).unwrap()).unwrap();
+        assert!((aligned.cast::<u8>().as_ptr() as usize).is_multiple_of(256), "Testing aligned allocations");
+        unsafe {
+            blocks.deallocate(second.cast(), Layout::new::<[u8; 16*100]>());
+            blocks.deallocate(aligned.cast(), Layout::from_size_align(16, 256).unwrap());
+        }
+        assert!(blocks.used_blocks() == 0 && blocks.largest_free_run() == 200, "Testing everything was freed");
+    }
+    #[test]
+    fn misaligned_test() {
+        let mut memory = vec![0u128; 6464/16];
+        let ptr = memory.as_mut_ptr().cast::<u8>().wrapping_add(8);
+        // every block starts 8 bytes past a multiple of 16
+        let blocks = unsafe { BitmapBlockAllocator::from_raw(ptr, 6400, 64) };
+        assert!(blocks.malloc(Layout::from_size_align(16, 16).unwrap()) == Err(AllocError::InvalidLayout), "Testing an unreachable alignment fails instead of hanging");
+        assert!(blocks.malloc(Layout::from_size_align(16, 8).unwrap()).is_ok(), "Testing a reachable alignment");
+        // only every other block is aligned to 16
+        let blocks = unsafe { BitmapBlockAllocator::from_raw(ptr, 24*100, 24) };
+        let first = blocks.malloc(Layout::from_size_align(16, 16).unwrap()).unwrap();
+        let second = blocks.malloc(Layout::from_size_align(16, 16).unwrap()).unwrap();
+        assert!(first.cast::<u8>().as_ptr() == ptr.wrapping_add(24) && second.cast::<u8>().as_ptr() == ptr.wrapping_add(72), "Testing aligned blocks are stepped through");
+        let whole = blocks.malloc(Layout::from_size_align(24*96, 16).unwrap());
+        assert!(matches!(whole, Err(AllocError::OutOfMemory { .. })), "Testing a run past the last aligned block fails");
+    }
+}
\ No newline at end of file
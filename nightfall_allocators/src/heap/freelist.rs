@@ -0,0 +1,479 @@
+use std::{alloc::{Allocator, Layout}, cell::Cell, ptr::NonNull};
+
+use crate::{arena::Arena, compose::Owns, error::AllocError};
+
+const WORD_SIZE: usize = std::mem::size_of::<usize>();
+const ALIGN_SIZE: usize = 16;
+/// header, footer and room for the free list links.
+const BLOCK_SIZE_MIN: usize = 2*WORD_SIZE + std::mem::size_of::<FreeLinks>();
+const BLOCK_USED_BIT: usize = 1;
+
+const _: () = assert!(BLOCK_SIZE_MIN.is_multiple_of(ALIGN_SIZE));
+
+/// Strategy used by [`FreeListAllocator`] to pick a free block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FitStrategy {
+    /// takes the first free block that's large enough, faster but fragments more.
+    #[default]
+    FirstFit,
+    /// takes the smallest free block that's large enough.
+    BestFit,
+}
+
+/// A block as seen by [`FreeListAllocator::walk`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeapBlock {
+    /// start of the block's payload.
+    pub address: NonNull<u8>,
+    /// usable size of the payload.
+    pub size: usize,
+    pub used: bool,
+}
+
+#[repr(C)]
+struct FreeLinks {
+    next: BlockPtr,
+    prev: BlockPtr,
+}
+
+/// Pointer to the header word of a block. Blocks start one word before a 16 byte
+/// boundary, so the payload that follows the header is always aligned.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+struct BlockPtr(*mut u8);
+
+impl BlockPtr {
+    fn null() -> Self {
+        Self(std::ptr::null_mut())
+    }
+    fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+    fn from_payload(ptr: *mut u8) -> Self {
+        Self(unsafe { ptr.sub(WORD_SIZE) })
+    }
+    fn payload(self) -> *mut u8 {
+        unsafe { self.0.add(WORD_SIZE) }
+    }
+    fn tag(self) -> usize {
+        unsafe { self.0.cast::<usize>().read() }
+    }
+    fn footer_tag(self) -> usize {
+        unsafe { self.0.add(self.size() - WORD_SIZE).cast::<usize>().read() }
+    }
+    fn size(self) -> usize {
+        self.tag() & !BLOCK_USED_BIT
+    }
+    fn is_used(self) -> bool {
+        self.tag() & BLOCK_USED_BIT != 0
+    }
+    /// writes both boundary tags of the block.
+    fn set(self, size: usize, used: bool) {
+        let tag = size | if used { BLOCK_USED_BIT } else { 0 };
+        unsafe {
+            self.0.cast::<usize>().write(tag);
+            self.0.add(size - WORD_SIZE).cast::<usize>().write(tag);
+        }
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn links(&self) -> &mut FreeLinks {
+        unsafe { &mut *self.payload().cast::<FreeLinks>() }
+    }
+    fn next_phys(self) -> BlockPtr {
+        BlockPtr(unsafe { self.0.add(self.size()) })
+    }
+    /// reads the footer of the previous physical block, the caller has to make
+    /// sure this isn't the first block.
+    fn prev_phys(self) -> BlockPtr {
+        let tag = unsafe { self.0.sub(WORD_SIZE).cast::<usize>().read() };
+        BlockPtr(unsafe { self.0.sub(tag & !BLOCK_USED_BIT) })
+    }
+}
+
+/// Variable size heap using boundary tags and an explicit free list.
+/// # Concepts
+/// Every block stores its size in a header and a footer, so both physical neighbours
+/// of a block can be found in constant time. When a block is freed it's immediately
+/// merged with any free neighbour, which keeps the free list short and fragmentation low.
+/// ```text
+/// ┌──────┬─────────┬──────┬──────┬─────────┬──────┬─────┐
+/// │ size │ payload │ size │ size │  free   │ size │ end │
+/// └──────┴─────────┴──────┴──────┴─────────┴──────┴─────┘
+/// ```
+/// It sits between an [`Arena`], which can't free individual allocations, and a
+/// [`crate::pool::Pool`], which only hands out a single size.
+pub struct FreeListAllocator {
+    /// first block of the heap.
+    start: *mut u8,
+    /// zero sized, always used, block that marks the end of the heap.
+    end: *mut u8,
+    free: Cell<BlockPtr>,
+    /// sum of the payload sizes of every free block.
+    available: Cell<usize>,
+    strategy: FitStrategy,
+}
+
+impl FreeListAllocator {
+    /// # Safety
+    /// `ptr` must be valid for reads and writes of `size` bytes for as long as the allocator is used.
+    /// If the region is too small to hold a block, every allocation fails.
+    pub unsafe fn from_raw(ptr: *mut u8, size: usize, strategy: FitStrategy) -> Self {
+        // the payload after the header word has to land on an ALIGN_SIZE boundary
+        let offset = ptr.wrapping_add(WORD_SIZE).align_offset(ALIGN_SIZE);
+        let heap_size = size.checked_sub(offset + WORD_SIZE).map(|size| size - size % ALIGN_SIZE).unwrap_or(0);
+        if heap_size < BLOCK_SIZE_MIN {
+            return Self { start: std::ptr::null_mut(), end: std::ptr::null_mut(), free: Cell::new(BlockPtr::null()), available: Cell::new(0), strategy };
+        }
+        let block = BlockPtr(unsafe { ptr.add(offset) });
+        block.set(heap_size, false);
+        let end = block.next_phys();
+        unsafe { end.0.cast::<usize>().write(BLOCK_USED_BIT) };
+        let heap = Self { start: block.0, end: end.0, free: Cell::new(BlockPtr::null()), available: Cell::new(0), strategy };
+        heap.push_free(block);
+        heap
+    }
+    /// # Safety
+    /// `slice` must outlive the allocator and not be accessed while the allocator is used.
+    pub unsafe fn from_slice(slice: &mut [u8], strategy: FitStrategy) -> Self {
+        unsafe { Self::from_raw(slice.as_mut_ptr(), slice.len(), strategy) }
+    }
+    pub fn from_arena(arena: &dyn Arena<Allocation = NonNull<[u8]>>, size: usize, strategy: FitStrategy) -> Result<Self, AllocError> {
+        let ptr = arena.arena_alloc(Layout::from_size_align(size, ALIGN_SIZE)?)?;
+        Ok(unsafe { Self::from_raw(ptr.as_ptr().cast(), ptr.len(), strategy) })
+    }
+    pub fn strategy(&self) -> FitStrategy {
+        self.strategy
+    }
+    /// amount of free bytes in the heap, a single allocation may not be able to use all of them.
+    pub fn available(&self) -> usize {
+        self.available.get()
+    }
+    /// Allocates a block fitting `layout`. The returned slice may be larger than requested.
+    pub fn malloc(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let out_of_memory = AllocError::OutOfMemory { requested: layout.size(), available: self.available() };
+        let size = match Self::block_size(layout.size()) {
+            Some(size) => size,
+            None => Err(out_of_memory)?,
+        };
+        let mut found: Option<(BlockPtr, usize)> = None;
+        let mut current = self.free.get();
+        while !current.is_null() {
+            if let Some(gap) = Self::fit(current, layout.align(), size) {
+                match self.strategy {
+                    FitStrategy::FirstFit => {
+                        found = Some((current, gap));
+                        break;
+                    }
+                    FitStrategy::BestFit => {
+                        if found.is_none_or(|(block, _)| current.size() < block.size()) {
+                            found = Some((current, gap));
+                            if current.size() == size + gap {
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            current = current.links().next;
+        }
+        let Some((mut block, gap)) = found else {
+            Err(out_of_memory)?
+        };
+        self.remove_free(block);
+        if gap != 0 {
+            // give the memory in front of the aligned payload back as its own free block
+            let total = block.size();
+            block.set(gap, false);
+            self.push_free(block);
+            block = BlockPtr(unsafe { block.0.add(gap) });
+            block.set(total - gap, false);
+        }
+        self.place(block, size);
+        Ok(Self::payload_slice(block))
+    }
+    /// Returns a block to the heap, merging it with its free neighbours.
+    /// # Safety
+    /// `ptr` must have been returned by [`FreeListAllocator::malloc`] on this allocator and not freed yet.
+    pub unsafe fn free(&self, ptr: NonNull<u8>) {
+        let block = BlockPtr::from_payload(ptr.as_ptr());
+        debug_assert!(block.is_used(), "block freed twice");
+        self.release(block, block.size());
+    }
+    /// Iterates over every block of the heap in address order.
+    pub fn walk(&self) -> HeapWalk<'_> {
+        HeapWalk { heap: self, current: BlockPtr(self.start) }
+    }
+    /// Checks the boundary tags and the free list for consistency, useful to find
+    /// the first corrupted block after an out of bounds write.
+    pub fn validate(&self) -> Result<(), AllocError> {
+        let corrupted = |block: BlockPtr, reason| AllocError::Corrupted { address: block.0 as usize, reason };
+        let mut free_blocks = 0;
+        let mut previous_free = false;
+        let mut current = BlockPtr(self.start);
+        while current.0 != self.end {
+            if current.0 > self.end || current.size() < BLOCK_SIZE_MIN || !current.size().is_multiple_of(ALIGN_SIZE) {
+                Err(corrupted(current, "invalid block size"))?
+            }
+            if current.tag() != current.footer_tag() {
+                Err(corrupted(current, "header and footer don't match"))?
+            }
+            if !current.is_used() {
+                if previous_free {
+                    Err(corrupted(current, "adjacent free blocks weren't merged"))?
+                }
+                free_blocks += 1;
+            }
+            previous_free = !current.is_used();
+            current = current.next_phys();
+        }
+        let mut listed = 0;
+        let mut prev = BlockPtr::null();
+        let mut current = self.free.get();
+        while !current.is_null() {
+            if current.0 < self.start || current.0 >= self.end {
+                Err(corrupted(current, "free list points outside of the heap"))?
+            }
+            if current.is_used() {
+                Err(corrupted(current, "used block in the free list"))?
+            }
+            if current.links().prev != prev {
+                Err(corrupted(current, "broken free list link"))?
+            }
+            listed += 1;
+            if listed > free_blocks {
+                Err(corrupted(current, "free list doesn't match the heap"))?
+            }
+            prev = current;
+            current = current.links().next;
+        }
+        if listed != free_blocks {
+            Err(corrupted(BlockPtr(self.start), "free list doesn't match the heap"))?
+        }
+        Ok(())
+    }
+    /// size of a block holding `size` bytes of payload.
+    fn block_size(size: usize) -> Option<usize> {
+        Some(size.checked_add(2*WORD_SIZE)?.checked_next_multiple_of(ALIGN_SIZE)?.max(BLOCK_SIZE_MIN))
+    }
+    /// returns the gap needed in front of `block` to align its payload, if the block fits.
+    fn fit(block: BlockPtr, align: usize, size: usize) -> Option<usize> {
+        let payload = block.payload() as usize;
+        let mut gap = payload.next_multiple_of(align) - payload;
+        if gap != 0 && gap < BLOCK_SIZE_MIN {
+            gap = (payload + BLOCK_SIZE_MIN).next_multiple_of(align) - payload;
+        }
+        (block.size() >= size.checked_add(gap)?).then_some(gap)
+    }
+    fn payload_slice(block: BlockPtr) -> NonNull<[u8]> {
+        NonNull::slice_from_raw_parts(NonNull::new(block.payload()).unwrap(), block.size() - 2*WORD_SIZE)
+    }
+    /// marks a block that isn't in the free list as used, splitting off what isn't needed.
+    fn place(&self, block: BlockPtr, size: usize) {
+        let total = block.size();
+        if total - size >= BLOCK_SIZE_MIN {
+            block.set(size, true);
+            let rest = block.next_phys();
+            rest.set(total - size, false);
+            self.push_free(rest);
+        } else {
+            block.set(total, true);
+        }
+    }
+    /// frees `size` bytes starting at `block` and merges them with the neighbours.
+    fn release(&self, mut block: BlockPtr, mut size: usize) {
+        let next = BlockPtr(unsafe { block.0.add(size) });
+        if !next.is_used() {
+            self.remove_free(next);
+            size += next.size();
+        }
+        if block.0 != self.start {
+            let prev = block.prev_phys();
+            if !prev.is_used() {
+                self.remove_free(prev);
+                size += prev.size();
+                block = prev;
+            }
+        }
+        block.set(size, false);
+        self.push_free(block);
+    }
+    fn push_free(&self, block: BlockPtr) {
+        let head = self.free.get();
+        let links = block.links();
+        links.next = head;
+        links.prev = BlockPtr::null();
+        if !head.is_null() {
+            head.links().prev = block;
+        }
+        self.free.set(block);
+        self.available.set(self.available.get() + block.size() - 2*WORD_SIZE);
+    }
+    fn remove_free(&self, block: BlockPtr) {
+        let FreeLinks { next, prev } = *block.links();
+        self.available.set(self.available.get() - (block.size() - 2*WORD_SIZE));
+        if !next.is_null() {
+            next.links().prev = prev;
+        }
+        if prev.is_null() {
+            self.free.set(next);
+        } else {
+            prev.links().next = next;
+        }
+    }
+}
+
+unsafe impl Allocator for FreeListAllocator {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.malloc(layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        unsafe { self.free(ptr) }
+    }
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let block = BlockPtr::from_payload(ptr.as_ptr());
+        let size = Self::block_size(new_layout.size()).ok_or(std::alloc::AllocError)?;
+        if (ptr.as_ptr() as usize).is_multiple_of(new_layout.align()) {
+            if block.size() >= size {
+                return Ok(Self::payload_slice(block));
+            }
+            // grow into the next block if it's free and large enough
+            let next = block.next_phys();
+            if !next.is_used() && block.size() + next.size() >= size {
+                self.remove_free(next);
+                block.set(block.size() + next.size(), false);
+                self.place(block, size);
+                return Ok(Self::payload_slice(block));
+            }
+        }
+        let new = self.allocate(new_layout)?;
+        unsafe {
+            std::ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr().cast::<u8>(), old_layout.size());
+            self.deallocate(ptr, old_layout);
+        }
+        Ok(new)
+    }
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let new = unsafe { self.grow(ptr, old_layout, new_layout)? };
+        unsafe { new.cast::<u8>().add(old_layout.size()).write_bytes(0, new.len() - old_layout.size()) };
+        Ok(new)
+    }
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if !(ptr.as_ptr() as usize).is_multiple_of(new_layout.align()) {
+            let new = self.allocate(new_layout)?;
+            unsafe {
+                std::ptr::copy_nonoverlapping(ptr.as_ptr(), new.as_ptr().cast::<u8>(), new_layout.size());
+                self.deallocate(ptr, old_layout);
+            }
+            return Ok(new);
+        }
+        let block = BlockPtr::from_payload(ptr.as_ptr());
+        let size = Self::block_size(new_layout.size()).ok_or(std::alloc::AllocError)?;
+        let total = block.size();
+        if total - size >= BLOCK_SIZE_MIN {
+            block.set(size, true);
+            self.release(block.next_phys(), total - size);
+        }
+        Ok(Self::payload_slice(block))
+    }
+}
+
+impl Owns for FreeListAllocator {
+    fn owns(&self, ptr: NonNull<u8>, _: Layout) -> bool {
+        (self.start as usize..self.end as usize).contains(&(ptr.as_ptr() as usize))
+    }
+}
+
+/// Iterator over the blocks of a [`FreeListAllocator`], see [`FreeListAllocator::walk`].
+pub struct HeapWalk<'a> {
+    heap: &'a FreeListAllocator,
+    current: BlockPtr,
+}
+
+impl Iterator for HeapWalk<'_> {
+    type Item = HeapBlock;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.0 == self.heap.end {
+            return None;
+        }
+        let block = self.current;
+        self.current = block.next_phys();
+        Some(HeapBlock { address: NonNull::new(block.payload()).unwrap(), size: block.size() - 2*WORD_SIZE, used: block.is_used() })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::{Allocator, Layout};
+
+    use crate::arena::StandardArena;
+
+    use super::{FitStrategy, FreeListAllocator};
+    #[test]
+    fn coalesce_test() {
+        let mut memory = vec![0u8; 1024*4];
+        let heap = unsafe { FreeListAllocator::from_slice(&mut memory, FitStrategy::FirstFit) };
+        let a = heap.malloc(Layout::new::<[u8; 100]>()).unwrap();
+        let b = heap.malloc(Layout::new::<[u8; 200]>()).unwrap();
+        let c = heap.malloc(Layout::new::<[u8; 300]>()).unwrap();
+        assert!(heap.walk().filter(|block| block.used).count() == 3, "Testing three blocks are in use");
+        unsafe {
+            heap.free(a.cast());
+            heap.free(c.cast());
+            heap.validate().unwrap();
+            heap.free(b.cast());
+        }
+        heap.validate().unwrap();
+        assert!(heap.walk().count() == 1, "Testing every block was merged back together");
+    }
+    #[test]
+    fn strategy_test() {
+        for strategy in [FitStrategy::FirstFit, FitStrategy::BestFit] {
+            let mut memory = vec![0u8; 1024*4];
+            let heap = unsafe { FreeListAllocator::from_slice(&mut memory, strategy) };
+            let large = heap.malloc(Layout::new::<[u8; 512]>()).unwrap();
+            let _ = heap.malloc(Layout::new::<u64>()).unwrap();
+            let small = heap.malloc(Layout::new::<[u8; 64]>()).unwrap();
+            let _ = heap.malloc(Layout::new::<u64>()).unwrap();
+            unsafe {
+                heap.free(small.cast());
+                heap.free(large.cast());
+            }
+            // the most recently freed block is at the front of the free list
+            let alloc = heap.malloc(Layout::new::<[u8; 64]>()).unwrap();
+            let expected = match strategy {
+                FitStrategy::FirstFit => large,
+                FitStrategy::BestFit => small,
+            };
+            assert!(alloc.cast::<u8>() == expected.cast::<u8>(), "Testing {strategy:?} picks the expected block");
+            heap.validate().unwrap();
+        }
+    }
+    #[test]
+    fn alignment_test() {
+        let mut memory = vec![0u8; 1024*16];
+        let heap = unsafe { FreeListAllocator::from_slice(&mut memory, FitStrategy::BestFit) };
+        for align in [1, 8, 16, 64, 256, 4096] {
+            let alloc = heap.malloc(Layout::from_size_align(24, align).unwrap()).unwrap();
+            assert!((alloc.cast::<u8>().as_ptr() as usize).is_multiple_of(align), "Testing allocation is aligned to {align}");
+        }
+        heap.validate().unwrap();
+        assert!(heap.malloc(Layout::new::<[u8; 1024*16]>()).is_err(), "Testing exhaustion returns an error");
+    }
+    #[test]
+    fn grow_test() {
+        let arena = StandardArena::new(1024*8);
+        let heap = FreeListAllocator::from_arena(&arena, 1024*4, FitStrategy::FirstFit).unwrap();
+        let old = Layout::new::<[u8; 64]>();
+        let new = Layout::new::<[u8; 256]>();
+        let ptr = heap.allocate(old).unwrap();
+        let grown = unsafe { heap.grow(ptr.cast(), old, new).unwrap() };
+        assert!(grown.cast::<u8>() == ptr.cast::<u8>(), "Testing the allocation grew in place");
+        let shrunk = unsafe { heap.shrink(grown.cast(), new, old).unwrap() };
+        assert!(shrunk.cast::<u8>() == ptr.cast::<u8>(), "Testing the allocation shrunk in place");
+        heap.validate().unwrap();
+        let mut vector = Vec::<u32, &FreeListAllocator>::new_in(&heap);
+        vector.extend(0..512);
+        assert!(vector.iter().sum::<u32>() == 511*512/2, "Testing vectors grow correctly");
+    }
+}
@@ -0,0 +1,6 @@
+mod tlsf;
+mod freelist;
+mod bitmap;
+pub use tlsf::*;
+pub use freelist::*;
+pub use bitmap::*;
\ No newline at end of file
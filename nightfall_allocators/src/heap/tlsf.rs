@@ -0,0 +1,470 @@
+use std::{alloc::{Allocator, Layout}, cell::UnsafeCell, ptr::NonNull};
+
+use crate::error::AllocError;
+
+const ALIGN_SIZE_LOG2: usize = 4;
+const ALIGN_SIZE: usize = 1 << ALIGN_SIZE_LOG2;
+const SL_INDEX_COUNT_LOG2: usize = 4;
+const SL_INDEX_COUNT: usize = 1 << SL_INDEX_COUNT_LOG2;
+#[cfg(target_pointer_width = "64")]
+const FL_INDEX_MAX: usize = 32;
+#[cfg(not(target_pointer_width = "64"))]
+const FL_INDEX_MAX: usize = 30;
+const FL_INDEX_SHIFT: usize = SL_INDEX_COUNT_LOG2 + ALIGN_SIZE_LOG2;
+const FL_INDEX_COUNT: usize = FL_INDEX_MAX - FL_INDEX_SHIFT + 1;
+const SMALL_BLOCK_SIZE: usize = 1 << FL_INDEX_SHIFT;
+
+const BLOCK_HEADER_SIZE: usize = std::mem::size_of::<BlockHeader>();
+const BLOCK_SIZE_MIN: usize = ALIGN_SIZE;
+const BLOCK_SIZE_MAX: usize = 1 << FL_INDEX_MAX;
+
+const BLOCK_FREE_BIT: usize = 1 << 0;
+const BLOCK_PREV_FREE_BIT: usize = 1 << 1;
+const BLOCK_FLAG_BITS: usize = BLOCK_FREE_BIT | BLOCK_PREV_FREE_BIT;
+
+const _: () = assert!(std::mem::size_of::<FreeLinks>() <= BLOCK_SIZE_MIN);
+const _: () = assert!(BLOCK_HEADER_SIZE.is_multiple_of(ALIGN_SIZE));
+
+/// Header placed in front of every block. The lowest two bits of `size` are used
+/// as flags, since block sizes are always a multiple of [`ALIGN_SIZE`].
+#[repr(C, align(16))]
+struct BlockHeader {
+    /// only valid when the previous physical block is free.
+    prev_phys: *mut BlockHeader,
+    size: usize,
+}
+/// Free list links, stored in the payload of free blocks.
+#[repr(C)]
+struct FreeLinks {
+    next: BlockPtr,
+    prev: BlockPtr,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+struct BlockPtr(*mut BlockHeader);
+
+impl BlockPtr {
+    fn null() -> Self {
+        Self(std::ptr::null_mut())
+    }
+    fn is_null(self) -> bool {
+        self.0.is_null()
+    }
+    fn from_payload(ptr: *mut u8) -> Self {
+        Self(unsafe { ptr.sub(BLOCK_HEADER_SIZE).cast() })
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn header(&self) -> &mut BlockHeader {
+        unsafe { &mut *self.0 }
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn links(&self) -> &mut FreeLinks {
+        unsafe { &mut *self.payload().cast::<FreeLinks>() }
+    }
+    fn payload(self) -> *mut u8 {
+        unsafe { self.0.cast::<u8>().add(BLOCK_HEADER_SIZE) }
+    }
+    fn size(self) -> usize {
+        self.header().size & !BLOCK_FLAG_BITS
+    }
+    fn set_size(self, size: usize) {
+        let header = self.header();
+        header.size = size | (header.size & BLOCK_FLAG_BITS);
+    }
+    fn is_free(self) -> bool {
+        self.header().size & BLOCK_FREE_BIT != 0
+    }
+    fn set_free(self) {
+        self.header().size |= BLOCK_FREE_BIT;
+    }
+    fn set_used(self) {
+        self.header().size &= !BLOCK_FREE_BIT;
+    }
+    fn is_prev_free(self) -> bool {
+        self.header().size & BLOCK_PREV_FREE_BIT != 0
+    }
+    fn set_prev_free(self) {
+        self.header().size |= BLOCK_PREV_FREE_BIT;
+    }
+    fn set_prev_used(self) {
+        self.header().size &= !BLOCK_PREV_FREE_BIT;
+    }
+    fn prev_phys(self) -> BlockPtr {
+        BlockPtr(self.header().prev_phys)
+    }
+    fn next_phys(self) -> BlockPtr {
+        BlockPtr(unsafe { self.payload().add(self.size()).cast() })
+    }
+    /// links the next physical block back to this one and returns it.
+    fn link_next(self) -> BlockPtr {
+        let next = self.next_phys();
+        next.header().prev_phys = self.0;
+        next
+    }
+    fn mark_as_free(self) {
+        self.link_next().set_prev_free();
+        self.set_free();
+    }
+    fn mark_as_used(self) {
+        self.next_phys().set_prev_used();
+        self.set_used();
+    }
+    fn can_split(self, size: usize) -> bool {
+        self.size() >= size + BLOCK_HEADER_SIZE + BLOCK_SIZE_MIN
+    }
+    /// splits the block at `size`, returning the remaining free block. The caller is
+    /// responsible for setting the remaining block's previous-free flag.
+    fn split(self, size: usize) -> BlockPtr {
+        let remaining = BlockPtr(unsafe { self.payload().add(size).cast() });
+        let remaining_size = self.size() - (size + BLOCK_HEADER_SIZE);
+        unsafe { remaining.0.write(BlockHeader { prev_phys: self.0, size: remaining_size }) };
+        self.set_size(size);
+        remaining.mark_as_free();
+        remaining
+    }
+    /// absorbs the next physical block into this one.
+    fn absorb(self, next: BlockPtr) -> BlockPtr {
+        self.set_size(self.size() + next.size() + BLOCK_HEADER_SIZE);
+        self.link_next();
+        self
+    }
+}
+
+fn mapping_insert(size: usize) -> (usize, usize) {
+    if size < SMALL_BLOCK_SIZE {
+        (0, size / (SMALL_BLOCK_SIZE / SL_INDEX_COUNT))
+    } else {
+        let fl = size.ilog2() as usize;
+        let sl = (size >> (fl - SL_INDEX_COUNT_LOG2)) ^ SL_INDEX_COUNT;
+        (fl - (FL_INDEX_SHIFT - 1), sl)
+    }
+}
+/// same as [`mapping_insert`] but rounds the size up to the next second level list,
+/// so any block found in the resulting list is large enough.
+fn mapping_search(size: usize) -> (usize, usize) {
+    if size >= SMALL_BLOCK_SIZE {
+        let round = (1 << (size.ilog2() as usize - SL_INDEX_COUNT_LOG2)) - 1;
+        mapping_insert(size + round)
+    } else {
+        mapping_insert(size)
+    }
+}
+
+struct Control {
+    fl_bitmap: u32,
+    sl_bitmap: [u32; FL_INDEX_COUNT],
+    blocks: [[BlockPtr; SL_INDEX_COUNT]; FL_INDEX_COUNT],
+    /// sum of the sizes of every free block.
+    available: usize,
+}
+
+impl Control {
+    fn new() -> Self {
+        Self { fl_bitmap: 0, sl_bitmap: [0; FL_INDEX_COUNT], blocks: [[BlockPtr::null(); SL_INDEX_COUNT]; FL_INDEX_COUNT], available: 0 }
+    }
+    fn insert_free_block(&mut self, block: BlockPtr) {
+        let (fl, sl) = mapping_insert(block.size());
+        let head = self.blocks[fl][sl];
+        let links = block.links();
+        links.next = head;
+        links.prev = BlockPtr::null();
+        if !head.is_null() {
+            head.links().prev = block;
+        }
+        self.blocks[fl][sl] = block;
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmap[fl] |= 1 << sl;
+        self.available += block.size();
+    }
+    fn remove_free_block(&mut self, block: BlockPtr) {
+        let (fl, sl) = mapping_insert(block.size());
+        let FreeLinks { next, prev } = *block.links();
+        self.available -= block.size();
+        if !next.is_null() {
+            next.links().prev = prev;
+        }
+        if !prev.is_null() {
+            prev.links().next = next;
+        } else {
+            self.blocks[fl][sl] = next;
+            if next.is_null() {
+                self.sl_bitmap[fl] &= !(1 << sl);
+                if self.sl_bitmap[fl] == 0 {
+                    self.fl_bitmap &= !(1 << fl);
+                }
+            }
+        }
+    }
+    /// finds a free block of at least `size` bytes and removes it from its free list.
+    fn locate_free(&mut self, size: usize) -> BlockPtr {
+        let (mut fl, sl) = mapping_search(size);
+        if fl >= FL_INDEX_COUNT {
+            return BlockPtr::null();
+        }
+        let mut sl_map = self.sl_bitmap[fl] & (!0u32 << sl);
+        if sl_map == 0 {
+            let fl_map = self.fl_bitmap & (!0u32).checked_shl(fl as u32 + 1).unwrap_or(0);
+            if fl_map == 0 {
+                return BlockPtr::null();
+            }
+            fl = fl_map.trailing_zeros() as usize;
+            sl_map = self.sl_bitmap[fl];
+        }
+        let block = self.blocks[fl][sl_map.trailing_zeros() as usize];
+        self.remove_free_block(block);
+        block
+    }
+    fn merge_prev(&mut self, block: BlockPtr) -> BlockPtr {
+        if block.is_prev_free() {
+            let prev = block.prev_phys();
+            self.remove_free_block(prev);
+            prev.absorb(block)
+        } else {
+            block
+        }
+    }
+    fn merge_next(&mut self, block: BlockPtr) -> BlockPtr {
+        let next = block.next_phys();
+        if next.is_free() {
+            self.remove_free_block(next);
+            block.absorb(next)
+        } else {
+            block
+        }
+    }
+    /// gives back the end of a free block that isn't needed for `size`.
+    fn trim_free(&mut self, block: BlockPtr, size: usize) {
+        if block.can_split(size) {
+            let remaining = block.split(size);
+            remaining.set_prev_free();
+            self.insert_free_block(remaining);
+        }
+    }
+    /// gives back the first `gap` bytes of a free block, returning the block that follows them.
+    fn trim_free_leading(&mut self, block: BlockPtr, gap: usize) -> BlockPtr {
+        if block.can_split(gap - BLOCK_HEADER_SIZE) {
+            let remaining = block.split(gap - BLOCK_HEADER_SIZE);
+            remaining.set_prev_free();
+            self.insert_free_block(block);
+            remaining
+        } else {
+            block
+        }
+    }
+}
+
+/// Two-Level Segregated Fit allocator.
+/// # Concepts
+/// Free blocks are kept in segregated lists indexed by a first level (power of two)
+/// and a second level (linear subdivision of that power of two). Two bitmaps record
+/// which lists are non empty, so finding a fitting block is a couple of `trailing_zeros`
+/// instead of a search, and freeing a block merges it with its physical neighbours
+/// immediately. Both [`TlsfAllocator::malloc`] and [`TlsfAllocator::free`] run in
+/// bounded time, which makes it usable from real-time threads.
+///
+/// The allocator never owns memory itself, it manages the regions (pools) it's given
+/// through [`TlsfAllocator::from_raw`], [`TlsfAllocator::from_slice`] and
+/// [`TlsfAllocator::add_pool`].
+pub struct TlsfAllocator {
+    control: UnsafeCell<Control>,
+}
+
+impl Default for TlsfAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsfAllocator {
+    /// creates an allocator without any pools, every allocation fails until
+    /// [`TlsfAllocator::add_pool`] is called.
+    pub fn new() -> Self {
+        Self { control: UnsafeCell::new(Control::new()) }
+    }
+    /// # Safety
+    /// `ptr` must be valid for reads and writes of `size` bytes for as long as the allocator is used.
+    /// If the region is too small to hold a pool, every allocation fails.
+    pub unsafe fn from_raw(ptr: *mut u8, size: usize) -> Self {
+        let tlsf = Self::new();
+        // a region too small for a pool simply leaves the allocator empty
+        let _ = unsafe { tlsf.add_pool(ptr, size) };
+        tlsf
+    }
+    /// # Safety
+    /// `slice` must outlive the allocator and not be accessed while the allocator is used.
+    pub unsafe fn from_slice(slice: &mut [u8]) -> Self {
+        unsafe { Self::from_raw(slice.as_mut_ptr(), slice.len()) }
+    }
+    /// Adds another region for the allocator to hand out. Pools are never merged with each
+    /// other, so a single allocation can't span two pools.
+    /// # Safety
+    /// `ptr` must be valid for reads and writes of `size` bytes for as long as the allocator is used,
+    /// and must not overlap any other pool.
+    pub unsafe fn add_pool(&self, ptr: *mut u8, size: usize) -> Result<(), AllocError> {
+        let start = ptr.align_offset(ALIGN_SIZE);
+        let pool_size = match size.checked_sub(start + 2*BLOCK_HEADER_SIZE) {
+            Some(size) => size - size % ALIGN_SIZE,
+            None => Err(AllocError::InvalidLayout)?,
+        };
+        if !(BLOCK_SIZE_MIN..BLOCK_SIZE_MAX).contains(&pool_size) {
+            Err(AllocError::InvalidLayout)?
+        }
+        let block = BlockPtr(unsafe { ptr.add(start).cast() });
+        unsafe { block.0.write(BlockHeader { prev_phys: std::ptr::null_mut(), size: pool_size }) };
+        block.set_free();
+        self.control().insert_free_block(block);
+        // zero sized sentinel block at the end of the pool, it's never free, so merging stops there
+        let sentinel = block.next_phys();
+        unsafe { sentinel.0.write(BlockHeader { prev_phys: block.0, size: 0 }) };
+        sentinel.set_prev_free();
+        Ok(())
+    }
+    /// Allocates a block fitting `layout`. The returned slice may be larger than requested.
+    pub fn malloc(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+        let control = self.control();
+        let out_of_memory = AllocError::OutOfMemory { requested: layout.size(), available: control.available };
+        let size = match Self::adjust_request_size(layout.size()) {
+            Some(size) => size,
+            None => Err(out_of_memory)?,
+        };
+        let block = if layout.align() <= ALIGN_SIZE {
+            control.locate_free(size)
+        } else {
+            // leave enough room in front of the aligned payload to form a free block
+            let gap_minimum = BLOCK_HEADER_SIZE + BLOCK_SIZE_MIN;
+            let aligned_size = match size.checked_add(layout.align() + gap_minimum) {
+                Some(size) if size < BLOCK_SIZE_MAX => size,
+                _ => Err(out_of_memory)?,
+            };
+            let block = control.locate_free(aligned_size);
+            if block.is_null() {
+                Err(out_of_memory)?
+            }
+            let payload = block.payload() as usize;
+            let mut gap = payload.next_multiple_of(layout.align()) - payload;
+            if gap != 0 && gap < gap_minimum {
+                gap = (payload + gap_minimum).next_multiple_of(layout.align()) - payload;
+            }
+            if gap != 0 {
+                control.trim_free_leading(block, gap)
+            } else {
+                block
+            }
+        };
+        if block.is_null() {
+            Err(out_of_memory)?
+        }
+        control.trim_free(block, size);
+        block.mark_as_used();
+        Ok(NonNull::slice_from_raw_parts(NonNull::new(block.payload()).unwrap(), block.size()))
+    }
+    /// Returns a block to the allocator, merging it with free neighbours.
+    /// # Safety
+    /// `ptr` must have been returned by [`TlsfAllocator::malloc`] on this allocator and not freed yet.
+    pub unsafe fn free(&self, ptr: NonNull<u8>) {
+        let control = self.control();
+        let mut block = BlockPtr::from_payload(ptr.as_ptr());
+        debug_assert!(!block.is_free(), "block freed twice");
+        block.mark_as_free();
+        block = control.merge_prev(block);
+        block = control.merge_next(block);
+        control.insert_free_block(block);
+    }
+    /// amount of free bytes across every pool, a single allocation may not be able to use all of them.
+    pub fn available(&self) -> usize {
+        self.control().available
+    }
+    fn adjust_request_size(size: usize) -> Option<usize> {
+        let aligned = size.checked_next_multiple_of(ALIGN_SIZE)?;
+        if aligned >= BLOCK_SIZE_MAX {
+            None
+        } else {
+            Some(aligned.max(BLOCK_SIZE_MIN))
+        }
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn control(&self) -> &mut Control {
+        unsafe { self.control.get().as_mut().unwrap() }
+    }
+}
+
+unsafe impl Allocator for TlsfAllocator {
+    #[inline]
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        self.malloc(layout).map_err(|_|std::alloc::AllocError)
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        unsafe { self.free(ptr) }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::{Allocator, Layout}, ptr::NonNull};
+
+    use crate::error::AllocError;
+
+    use super::TlsfAllocator;
+    #[test]
+    fn tlsf_test() {
+        let mut memory = vec![0u8; 1024*64];
+        let tlsf = unsafe { TlsfAllocator::from_slice(&mut memory) };
+        let a = tlsf.malloc(Layout::new::<[u8; 100]>()).unwrap();
+        let b = tlsf.malloc(Layout::new::<[u8; 3000]>()).unwrap();
+        assert!(a.len() >= 100 && b.len() >= 3000, "Testing the blocks are large enough");
+        assert!(a.cast::<u8>().as_ptr() != b.cast::<u8>().as_ptr(), "Testing the blocks don't alias");
+        unsafe {
+            tlsf.free(a.cast());
+            tlsf.free(b.cast());
+        }
+        // everything got merged back, so most of the pool can be handed out again
+        let all = tlsf.malloc(Layout::from_size_align(1024*56, 1).unwrap());
+        assert!(all.is_ok(), "Testing freed blocks are coalesced");
+        let error = tlsf.malloc(Layout::from_size_align(1024*16, 1).unwrap()).unwrap_err();
+        assert!(matches!(error, AllocError::OutOfMemory { requested: 16384, available } if available == tlsf.available()), "Testing exhaustion reports what's left: {error}");
+    }
+    #[test]
+    fn alignment_test() {
+        let mut memory = vec![0u8; 1024*64];
+        let tlsf = unsafe { TlsfAllocator::from_slice(&mut memory) };
+        let mut allocations = Vec::new();
+        for align in [1, 8, 16, 64, 256, 4096] {
+            let alloc = tlsf.malloc(Layout::from_size_align(24, align).unwrap()).unwrap();
+            assert!((alloc.cast::<u8>().as_ptr() as usize).is_multiple_of(align), "Testing allocation is aligned to {align}");
+            allocations.push(alloc);
+        }
+        for alloc in allocations {
+            unsafe { tlsf.free(alloc.cast()) };
+        }
+        assert!(tlsf.malloc(Layout::from_size_align(1024*56, 1).unwrap()).is_ok(), "Testing aligned blocks are coalesced");
+    }
+    #[test]
+    fn pool_test() {
+        let mut first = vec![0u8; 1024];
+        let mut second = vec![0u8; 1024*8];
+        let tlsf = unsafe { TlsfAllocator::from_slice(&mut first) };
+        assert!(tlsf.malloc(Layout::new::<[u8; 4096]>()).is_err(), "Testing a pool that's too small");
+        unsafe { tlsf.add_pool(second.as_mut_ptr(), second.len()).unwrap() };
+        let alloc = tlsf.malloc(Layout::new::<[u8; 4096]>()).unwrap();
+        let range = second.as_ptr_range();
+        assert!(range.contains(&alloc.cast::<u8>().as_ptr().cast_const()), "Testing allocation comes from the added pool");
+        assert!(TlsfAllocator::new().malloc(Layout::new::<u8>()).is_err(), "Testing an empty allocator");
+        let mut tiny = [0u8; 8];
+        assert!(unsafe { tlsf.add_pool(tiny.as_mut_ptr(), tiny.len()) }.is_err(), "Testing a region that can't hold a pool");
+    }
+    #[test]
+    fn allocator_test() {
+        let mut memory = vec![0u8; 1024*64];
+        let tlsf = unsafe { TlsfAllocator::from_slice(&mut memory) };
+        let mut vector = Vec::<u64, &TlsfAllocator>::new_in(&tlsf);
+        for i in 0..1000 {
+            vector.push(i);
+        }
+        assert!(vector.iter().sum::<u64>() == 999*1000/2, "Testing vectors grow correctly");
+        let boxed = Box::new_in(42u32, &tlsf);
+        assert!(*boxed == 42, "Testing box allocated correctly");
+        let ptr = tlsf.allocate(Layout::new::<u128>()).unwrap();
+        unsafe { tlsf.deallocate(NonNull::new(ptr.as_ptr().cast()).unwrap(), Layout::new::<u128>()) };
+    }
+}
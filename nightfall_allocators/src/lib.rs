@@ -1,4 +1,9 @@
 #![feature(allocator_api)]
 pub mod pool;
 pub mod arena;
+pub mod heap;
+pub mod global;
+pub mod debug;
+pub mod compose;
+pub mod trace;
 pub mod error;
\ No newline at end of file
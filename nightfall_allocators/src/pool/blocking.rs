@@ -0,0 +1,185 @@
+use std::{future::Future, pin::Pin, sync::{Condvar, Mutex, MutexGuard}, task::{Context, Poll, Waker}, time::{Duration, Instant}};
+
+use crate::error::AllocError;
+
+use super::PoolAllocator;
+
+struct State<P> {
+    pool: P,
+    /// tasks waiting in [`BlockingPool::acquire_async`].
+    wakers: Vec<Waker>,
+}
+
+/// Thread safe wrapper around a bounded [`PoolAllocator`] that can wait for an allocation
+/// to be released instead of failing with [`AllocError::OutOfMemory`].
+/// # Concepts
+/// Every release wakes up all waiting threads and tasks, which then race for the freed
+/// allocation. That way a waiter that gave up can't swallow a wakeup meant for another one.
+/// It makes the pool usable as a semaphore for connections or buffers.
+/// ```
+/// use std::time::Duration;
+/// use nightfall_allocators::pool::{BlockingPool, Pool, PoolAllocator};
+///
+/// let mut slots = [0u64; 1];
+/// let pool = BlockingPool::new(unsafe { Pool::from_slice(&mut slots) });
+/// let slot = pool.acquire_blocking(Duration::from_millis(10)).unwrap();
+/// assert!(pool.acquire_blocking(Duration::from_millis(10)).is_err());
+/// pool.deallocate(slot).unwrap();
+/// ```
+pub struct BlockingPool<P: PoolAllocator> {
+    state: Mutex<State<P>>,
+    released: Condvar,
+}
+
+impl<P: PoolAllocator> BlockingPool<P> {
+    pub fn new(pool: P) -> Self {
+        Self { state: Mutex::new(State { pool, wakers: Vec::new() }), released: Condvar::new() }
+    }
+    pub fn into_inner(self) -> P {
+        self.state.into_inner().unwrap().pool
+    }
+    /// allocates without waiting.
+    pub fn try_acquire(&self) -> Result<P::Allocation, AllocError> {
+        self.lock().pool.allocate()
+    }
+    /// Waits up to `timeout` for an allocation, returning the last error if none was released in time.
+    /// A timeout too long to represent waits forever.
+    pub fn acquire_blocking(&self, timeout: Duration) -> Result<P::Allocation, AllocError> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.lock();
+        loop {
+            match state.pool.allocate() {
+                Err(AllocError::OutOfMemory { .. }) => {}
+                result => return result,
+            }
+            let Some(deadline) = deadline else {
+                state = self.released.wait(state).unwrap();
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return state.pool.allocate();
+            }
+            state = self.released.wait_timeout(state, deadline - now).unwrap().0;
+        }
+    }
+    /// Returns a future that resolves once an allocation is available. It works with any
+    /// executor, since it only relies on the waker it's polled with.
+    pub fn acquire_async(&self) -> Acquire<'_, P> {
+        Acquire { pool: self }
+    }
+    /// Returns `allocation` to the pool and wakes up everyone waiting for one.
+    pub fn release(&self, allocation: P::Allocation) -> Result<(), AllocError> {
+        let mut state = self.lock();
+        state.pool.deallocate(allocation)?;
+        let wakers = std::mem::take(&mut state.wakers);
+        drop(state);
+        self.released.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+        Ok(())
+    }
+    fn lock(&self) -> MutexGuard<'_, State<P>> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl<P: PoolAllocator> PoolAllocator for BlockingPool<P> {
+    type Allocation = P::Allocation;
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
+        self.try_acquire()
+    }
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
+        self.release(allocation)
+    }
+}
+
+/// Future returned by [`BlockingPool::acquire_async`].
+pub struct Acquire<'a, P: PoolAllocator> {
+    pool: &'a BlockingPool<P>,
+}
+
+impl<P: PoolAllocator> Future for Acquire<'_, P> {
+    type Output = Result<P::Allocation, AllocError>;
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // the waker is registered under the same lock a release takes, so no wakeup is lost
+        let mut state = self.pool.lock();
+        match state.pool.allocate() {
+            Err(AllocError::OutOfMemory { .. }) => {
+                if !state.wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                    state.wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            result => Poll::Ready(result),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{future::Future, pin::pin, sync::{Arc, atomic::{AtomicBool, Ordering}}, task::{Context, Poll, Wake, Waker}, thread::Thread, time::Duration};
+
+    use crate::{error::AllocError, pool::{Pool, PoolAllocator}};
+
+    use super::BlockingPool;
+
+    struct ThreadWaker(Thread, AtomicBool);
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.1.store(true, Ordering::Release);
+            self.0.unpark();
+        }
+    }
+    fn block_on<F: Future>(future: F) -> (F::Output, bool) {
+        let waker = Arc::new(ThreadWaker(std::thread::current(), AtomicBool::new(false)));
+        let task = Waker::from(waker.clone());
+        let mut context = Context::from_waker(&task);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return (output, waker.1.load(Ordering::Acquire));
+            }
+            std::thread::park();
+        }
+    }
+    #[test]
+    fn blocking_test() {
+        let mut slots = [0u64; 2];
+        let pool = BlockingPool::new(unsafe { Pool::from_slice(&mut slots) });
+        let a = pool.acquire_blocking(Duration::ZERO).unwrap();
+        let b = pool.try_acquire().unwrap();
+        let error = pool.acquire_blocking(Duration::from_millis(20)).unwrap_err();
+        assert!(matches!(error, AllocError::OutOfMemory { .. }), "Testing waiting times out");
+        let b = b.as_ptr() as usize;
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.acquire_blocking(Duration::from_secs(10)).map(|ptr| ptr.as_ptr() as usize));
+            std::thread::sleep(Duration::from_millis(20));
+            pool.release(std::ptr::NonNull::new(b as *mut u64).unwrap()).unwrap();
+            assert!(waiter.join().unwrap() == Ok(b), "Testing a blocked thread gets the released slot");
+        });
+        pool.release(a).unwrap();
+        let a = pool.acquire_blocking(Duration::MAX).unwrap();
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| pool.acquire_blocking(Duration::MAX).map(|ptr| ptr.as_ptr() as usize));
+            std::thread::sleep(Duration::from_millis(20));
+            pool.release(a).unwrap();
+            assert!(waiter.join().unwrap() == Ok(a.as_ptr() as usize), "Testing an unbounded timeout waits for a release");
+        });
+    }
+    #[test]
+    fn async_test() {
+        let mut slots = [0u64; 1];
+        let pool = BlockingPool::new(unsafe { Pool::from_slice(&mut slots) });
+        let (slot, woken) = block_on(pool.acquire_async());
+        let slot = slot.unwrap().as_ptr() as usize;
+        assert!(!woken, "Testing a free slot is acquired immediately");
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                pool.deallocate(std::ptr::NonNull::new(slot as *mut u64).unwrap()).unwrap();
+            });
+            let (acquired, woken) = block_on(pool.acquire_async());
+            assert!(acquired.unwrap().as_ptr() as usize == slot && woken, "Testing the future is woken by a release");
+        });
+    }
+}
\ No newline at end of file
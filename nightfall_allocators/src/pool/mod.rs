@@ -1,42 +1,48 @@
 use std::{rc::Rc, sync::Arc};
+
+use crate::error::AllocError;
 mod ptr;
+mod object;
+mod blocking;
 pub use ptr::*;
+pub use object::*;
+pub use blocking::*;
 
 pub trait PoolAllocator {
     type Allocation;
-    fn allocate(&self) -> anyhow::Result<Self::Allocation>;
-    fn deallocate(&self, allocation: Self::Allocation) -> anyhow::Result<()>;
+    fn allocate(&self) -> Result<Self::Allocation, AllocError>;
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError>;
 }
 pub trait PoolAllocatorGuarded: PoolAllocator {
     type Guard;
-    fn allocate_guarded(&self) -> anyhow::Result<Self::Guard>;
+    fn allocate_guarded(&self) -> Result<Self::Guard, AllocError>;
 }
 
 impl<T: PoolAllocator> PoolAllocator for Arc<T> {
     type Allocation = T::Allocation;
-    fn allocate(&self) -> anyhow::Result<Self::Allocation> {
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
         (**self).allocate()
     }
-    fn deallocate(&self, allocation: Self::Allocation) -> anyhow::Result<()> {
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
         (**self).deallocate(allocation)
     }
 }
 
 impl<T: PoolAllocator> PoolAllocator for Rc<T> {
     type Allocation = T::Allocation;
-    fn allocate(&self) -> anyhow::Result<Self::Allocation> {
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
         (**self).allocate()
     }
-    fn deallocate(&self, allocation: Self::Allocation) -> anyhow::Result<()> {
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
         (**self).deallocate(allocation)
     }
 }
 impl<T: PoolAllocator> PoolAllocator for Box<T> {
     type Allocation = T::Allocation;
-    fn allocate(&self) -> anyhow::Result<Self::Allocation> {
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
         (**self).allocate()
     }
-    fn deallocate(&self, allocation: Self::Allocation) -> anyhow::Result<()> {
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
         (**self).deallocate(allocation)
     }
 }
\ No newline at end of file
@@ -0,0 +1,201 @@
+use std::{mem::ManuallyDrop, ops::{Deref, DerefMut}};
+
+use crate::error::AllocError;
+
+use super::{PoolAllocator, PoolAllocatorGuarded};
+
+#[cfg(feature = "sync")]
+type IdleList<T> = std::sync::Mutex<Vec<T>>;
+#[cfg(not(feature = "sync"))]
+type IdleList<T> = std::cell::RefCell<Vec<T>>;
+
+/// Pool of reusable objects that are expensive to construct, like buffers or parsers.
+/// # Concepts
+/// Objects are created with the `factory` when no idle object is left. When an object
+/// comes back it's passed to `reset` and kept for the next caller, unless the pool already
+/// retains [`ObjectPool::max_idle`] objects, in which case it's dropped.
+/// ```
+/// use nightfall_allocators::pool::ObjectPool;
+///
+/// let pool = ObjectPool::new(|| Vec::<u8>::with_capacity(4096), |buffer| buffer.clear());
+/// {
+///     let mut buffer = pool.get();
+///     buffer.extend_from_slice(b"scratch");
+/// } // the buffer goes back to the pool here
+/// assert!(pool.get().is_empty() && pool.get().capacity() >= 4096);
+/// ```
+/// The pool is only thread safe with the `sync` feature enabled.
+pub struct ObjectPool<T, F: Fn() -> T, R: Fn(&mut T)> {
+    factory: F,
+    reset: R,
+    idle: IdleList<T>,
+    max_idle: usize,
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> ObjectPool<T, F, R> {
+    pub fn new(factory: F, reset: R) -> Self {
+        Self { factory, reset, idle: IdleList::new(Vec::new()), max_idle: usize::MAX }
+    }
+    /// caps the amount of idle objects the pool keeps around.
+    pub fn with_max_idle(mut self, max_idle: usize) -> Self {
+        self.max_idle = max_idle;
+        self
+    }
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+    /// amount of objects waiting to be reused.
+    pub fn idle_count(&self) -> usize {
+        self.idle_list().len()
+    }
+    /// creates objects until `count` are idle, or the cap is reached.
+    pub fn prefill(&self, count: usize) {
+        let count = count.min(self.max_idle);
+        while self.idle_count() < count {
+            let object = (self.factory)();
+            self.idle_list().push(object);
+        }
+    }
+    /// Takes an idle object, or creates one, returning it to the pool once the guard is dropped.
+    pub fn get(&self) -> Pooled<'_, T, F, R> {
+        Pooled { pool: self, object: ManuallyDrop::new(self.take()) }
+    }
+    fn take(&self) -> T {
+        // the lock is released before running the factory
+        let idle = self.idle_list().pop();
+        idle.unwrap_or_else(|| (self.factory)())
+    }
+    fn release(&self, mut object: T) {
+        (self.reset)(&mut object);
+        let mut idle = self.idle_list();
+        if idle.len() < self.max_idle {
+            idle.push(object);
+        }
+    }
+    #[cfg(feature = "sync")]
+    fn idle_list(&self) -> impl DerefMut<Target = Vec<T>> + '_ {
+        self.idle.lock().unwrap()
+    }
+    #[cfg(not(feature = "sync"))]
+    fn idle_list(&self) -> impl DerefMut<Target = Vec<T>> + '_ {
+        self.idle.borrow_mut()
+    }
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> PoolAllocator for ObjectPool<T, F, R> {
+    type Allocation = T;
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
+        Ok(self.take())
+    }
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
+        self.release(allocation);
+        Ok(())
+    }
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> PoolAllocator for &ObjectPool<T, F, R> {
+    type Allocation = T;
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
+        (**self).allocate()
+    }
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
+        (**self).deallocate(allocation)
+    }
+}
+
+impl<'a, T, F: Fn() -> T, R: Fn(&mut T)> PoolAllocatorGuarded for &'a ObjectPool<T, F, R> {
+    type Guard = Pooled<'a, T, F, R>;
+    fn allocate_guarded(&self) -> Result<Self::Guard, AllocError> {
+        Ok(self.get())
+    }
+}
+
+/// Object borrowed from an [`ObjectPool`], it's reset and returned to the pool when dropped.
+pub struct Pooled<'a, T, F: Fn() -> T, R: Fn(&mut T)> {
+    pool: &'a ObjectPool<T, F, R>,
+    object: ManuallyDrop<T>,
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> Pooled<'_, T, F, R> {
+    /// takes the object out of the pool for good.
+    pub fn detach(this: Self) -> T {
+        let mut this = ManuallyDrop::new(this);
+        unsafe { ManuallyDrop::take(&mut this.object) }
+    }
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> Deref for Pooled<'_, T, F, R> {
+    type Target = T;
+    fn deref(&self) -> &Self::Target {
+        &self.object
+    }
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> DerefMut for Pooled<'_, T, F, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.object
+    }
+}
+
+impl<T, F: Fn() -> T, R: Fn(&mut T)> Drop for Pooled<'_, T, F, R> {
+    fn drop(&mut self) {
+        let object = unsafe { ManuallyDrop::take(&mut self.object) };
+        self.pool.release(object);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use crate::pool::{PoolAllocator, PoolAllocatorGuarded};
+
+    use super::{ObjectPool, Pooled};
+    #[test]
+    fn reuse_test() {
+        let created = Cell::new(0);
+        let pool = ObjectPool::new(|| {
+            created.set(created.get() + 1);
+            Vec::<u32>::with_capacity(64)
+        }, |vector| vector.clear());
+        {
+            let mut a = pool.get();
+            a.push(7);
+            let _b = (&pool).allocate_guarded().unwrap();
+        }
+        assert!(pool.idle_count() == 2 && created.get() == 2, "Testing both objects went back to the pool");
+        let a = pool.get();
+        assert!(a.is_empty() && a.capacity() >= 64 && created.get() == 2, "Testing objects are reset and reused");
+        let detached = Pooled::detach(a);
+        drop(detached);
+        assert!(pool.idle_count() == 1, "Testing detached objects don't return");
+    }
+    #[test]
+    fn cap_test() {
+        let pool = ObjectPool::new(String::new, String::clear).with_max_idle(2);
+        pool.prefill(8);
+        assert!(pool.idle_count() == 2, "Testing prefilling respects the cap");
+        let objects: Vec<_> = (0..4).map(|_| pool.allocate().unwrap()).collect();
+        for object in objects {
+            pool.deallocate(object).unwrap();
+        }
+        assert!(pool.idle_count() == 2, "Testing objects past the cap are dropped");
+    }
+    #[cfg(feature = "sync")]
+    #[test]
+    fn sync_test() {
+        let pool = ObjectPool::new(|| vec![0u8; 256], |buffer| buffer.fill(0)).with_max_idle(4);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..100 {
+                        let mut buffer = pool.get();
+                        assert!(buffer.iter().all(|&byte| byte == 0), "Testing buffers come back reset");
+                        buffer[i] = 1;
+                    }
+                });
+            }
+        });
+        assert!(pool.idle_count() <= 4, "Testing the cap holds across threads");
+    }
+}
\ No newline at end of file
@@ -1,59 +1,268 @@
-use std::{alloc::Layout, any::type_name, cell::UnsafeCell, marker::PhantomData, ops::Div, ptr::NonNull};
+use std::{alloc::Layout, any::type_name, cell::{Cell, UnsafeCell}, marker::PhantomData, ops::Div, ptr::NonNull};
 
-use anyhow::Ok;
+use nightfall_collections::bitmap::BitSet;
 
 use crate::{arena::Arena, error::AllocError};
 
 use super::PoolAllocator;
 
+/// Pool of fixed size slots for values of `T`.
+/// # Concepts
+/// Freed slots are reused first, after that slots are handed out in address order.
+/// Which slots are live is tracked in a [`BitSet`], so the pool can tell a double free
+/// from a valid one and enumerate every live value, e.g. to sweep components each frame.
+///
+/// The pool never initializes or drops values by itself, [`Pool::iter`], [`Pool::iter_mut`]
+/// and [`Pool::drain`] rely on the caller having written a value to every live slot.
 pub struct Pool<T> {
     ptr: *mut u8,
     capacity: usize,
     free: UnsafeCell<Vec<usize>>,
+    /// first slot that has never been handed out.
+    next: Cell<usize>,
+    occupied: UnsafeCell<BitSet>,
+    len: Cell<usize>,
     marker_: PhantomData<T>,
 }
 
+// the pool only hands out pointers into its own slots, moving it moves ownership of every `T` in it
+unsafe impl<T: Send> Send for Pool<T> {}
+
 impl<T> Pool<T> {
     pub unsafe fn from_raw(ptr: *mut u8, size: usize) -> Self {
-        assert!(size % std::mem::size_of::<T>() == 0, "size must be aligned to {}", type_name::<T>());
-        Self { ptr, capacity: size.div(std::mem::size_of::<T>()), free: UnsafeCell::new(vec![0]), marker_: PhantomData }
+        assert!(size.is_multiple_of(std::mem::size_of::<T>()), "size must be aligned to {}", type_name::<T>());
+        Self::with_capacity(ptr, size.div(std::mem::size_of::<T>()))
     }
     pub unsafe fn from_slice(slice: &mut [T]) -> Self {
-        Self { ptr: slice.as_mut_ptr().cast(), capacity: slice.len(), free: UnsafeCell::new(vec![0]), marker_: PhantomData }
+        Self::with_capacity(slice.as_mut_ptr().cast(), slice.len())
     }
-    pub fn from_arena(arena: &dyn Arena<Allocation = *mut u8>, layout: Layout) -> anyhow::Result<Self> {
+    pub fn from_arena(arena: &dyn Arena<Allocation = NonNull<[u8]>>, layout: Layout) -> Result<Self, AllocError> {
         let ptr = arena.arena_alloc(layout)?;
-        Ok(Self { ptr, capacity: layout.size(), free: UnsafeCell::new(vec![0]), marker_: PhantomData })
+        Ok(Self::with_capacity(ptr.as_ptr().cast(), layout.size().div(std::mem::size_of::<T>())))
+    }
+    fn with_capacity(ptr: *mut u8, capacity: usize) -> Self {
+        Self {
+            ptr,
+            capacity,
+            free: UnsafeCell::new(Vec::new()),
+            next: Cell::new(0),
+            occupied: UnsafeCell::new(BitSet::new(capacity)),
+            len: Cell::new(0),
+            marker_: PhantomData,
+        }
     }
+    /// slots that were freed and will be handed out again before any untouched slot.
     pub fn free(&self) -> &mut Vec<usize> {
         unsafe { self.free.get().as_mut().unwrap() }
     }
     pub fn as_ptr(&self) -> *mut u8 {
         self.ptr
     }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    /// amount of live slots.
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+    /// whether `ptr` points to a slot of this pool that's currently allocated.
+    pub fn is_live(&self, ptr: NonNull<T>) -> bool {
+        self.slot(ptr).is_ok_and(|slot| self.occupied().active(slot))
+    }
+    /// Iterates over the value of every live slot in address order.
+    /// # Safety
+    /// Every live slot must hold an initialized `T` that isn't mutably borrowed during the iteration.
+    pub unsafe fn iter(&self) -> Iter<'_, T> {
+        Iter { pool: self, slot: 0 }
+    }
+    /// Iterates mutably over the value of every live slot in address order.
+    /// # Safety
+    /// Every live slot must hold an initialized `T` that isn't borrowed during the iteration.
+    pub unsafe fn iter_mut(&mut self) -> IterMut<'_, T> {
+        IterMut { pool: self, slot: 0 }
+    }
+    /// Moves the value out of every live slot and resets the pool. Values the iterator
+    /// doesn't get to are dropped when it's dropped.
+    /// # Safety
+    /// Every live slot must hold an initialized `T`, and no pointer handed out by the pool
+    /// may be used afterwards.
+    pub unsafe fn drain(&mut self) -> Drain<'_, T> {
+        Drain { pool: self, slot: 0 }
+    }
+    /// index of the slot `ptr` points to.
+    fn slot(&self, ptr: NonNull<T>) -> Result<usize, AllocError> {
+        let address = ptr.as_ptr() as usize;
+        let Some(distance) = address.checked_sub(self.as_ptr() as usize) else {
+            Err(AllocError::ForeignPointer { address })?
+        };
+        let slot = distance.div(std::mem::size_of::<T>());
+        if slot >= self.capacity || !distance.is_multiple_of(std::mem::size_of::<T>()) {
+            Err(AllocError::ForeignPointer { address })?
+        }
+        Ok(slot)
+    }
+    /// first live slot at or after `slot`.
+    fn next_live(&self, slot: usize) -> Option<usize> {
+        self.occupied().first_one(slot, self.next.get())
+    }
+    fn slot_ptr(&self, slot: usize) -> *mut T {
+        unsafe { self.as_ptr().cast::<T>().add(slot) }
+    }
+    #[allow(clippy::mut_from_ref)]
+    fn occupied(&self) -> &mut BitSet {
+        unsafe { self.occupied.get().as_mut().unwrap() }
+    }
 }
 
 impl<T> PoolAllocator for Pool<T> {
     type Allocation = NonNull<T>;
-    fn allocate(&self) -> anyhow::Result<Self::Allocation> {
-        if self.free().len() == 0 {
-            Err(AllocError::OutOfMemory)?
-        }
-        if let Some(alloc) = self.free().pop() {
-            if alloc >= self.capacity {
-                Err(AllocError::OutOfMemory)?
-            }
-            self.free().push(alloc+1);
-            unsafe {
-                Ok(NonNull::new(self.as_ptr().cast::<T>().add(alloc)).unwrap())
+    fn allocate(&self) -> Result<Self::Allocation, AllocError> {
+        let slot = match self.free().pop() {
+            Some(slot) => slot,
+            None if self.next.get() < self.capacity => {
+                self.next.set(self.next.get() + 1);
+                self.next.get() - 1
             }
-        } else {
-            Err(AllocError::OutOfMemory)?
-        }
+            None => Err(AllocError::OutOfMemory { requested: std::mem::size_of::<T>(), available: 0 })?,
+        };
+        self.occupied().flip(slot, true);
+        self.len.set(self.len.get() + 1);
+        Ok(NonNull::new(self.slot_ptr(slot)).unwrap())
     }
-    fn deallocate(&self, allocation: Self::Allocation) -> anyhow::Result<()> {
-        let offset = (allocation.as_ptr() as usize - self.as_ptr() as usize).div(std::mem::size_of::<T>());
-        self.free().push(offset);
+    fn deallocate(&self, allocation: Self::Allocation) -> Result<(), AllocError> {
+        let slot = self.slot(allocation)?;
+        if !self.occupied().active(slot) {
+            Err(AllocError::DoubleFree { address: allocation.as_ptr() as usize })?
+        }
+        self.occupied().flip(slot, false);
+        self.len.set(self.len.get() - 1);
+        self.free().push(slot);
         Ok(())
     }
+}
+
+/// Iterator over the live values of a [`Pool`], see [`Pool::iter`].
+pub struct Iter<'a, T> {
+    pool: &'a Pool<T>,
+    slot: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.pool.next_live(self.slot)?;
+        self.slot = slot + 1;
+        Some(unsafe { &*self.pool.slot_ptr(slot) })
+    }
+}
+
+/// Mutable iterator over the live values of a [`Pool`], see [`Pool::iter_mut`].
+pub struct IterMut<'a, T> {
+    pool: &'a mut Pool<T>,
+    slot: usize,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.pool.next_live(self.slot)?;
+        self.slot = slot + 1;
+        Some(unsafe { &mut *self.pool.slot_ptr(slot) })
+    }
+}
+
+/// Draining iterator over the live values of a [`Pool`], see [`Pool::drain`].
+pub struct Drain<'a, T> {
+    pool: &'a mut Pool<T>,
+    slot: usize,
+}
+
+impl<T> Iterator for Drain<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        let slot = self.pool.next_live(self.slot)?;
+        self.slot = slot + 1;
+        Some(unsafe { self.pool.slot_ptr(slot).read() })
+    }
+}
+
+impl<T> Drop for Drain<'_, T> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.pool.occupied().clear();
+        self.pool.free().clear();
+        self.pool.next.set(0);
+        self.pool.len.set(0);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{alloc::Layout, cell::Cell, rc::Rc};
+
+    use crate::{arena::StandardArena, error::AllocError};
+
+    use super::{Pool, PoolAllocator};
+    #[test]
+    fn error_test() {
+        let mut slots = [0u64; 4];
+        let pool = unsafe { Pool::from_slice(&mut slots) };
+        let a = pool.allocate().unwrap();
+        pool.deallocate(a).unwrap();
+        assert!(pool.deallocate(a) == Err(AllocError::DoubleFree { address: a.as_ptr() as usize }), "Testing double frees are detected");
+        let mut other = 0u64;
+        let foreign = std::ptr::NonNull::from(&mut other);
+        assert!(matches!(pool.deallocate(foreign), Err(AllocError::ForeignPointer { .. })), "Testing foreign pointers are rejected");
+    }
+    #[test]
+    fn reuse_test() {
+        let mut slots = [0u64; 3];
+        let pool = unsafe { Pool::from_slice(&mut slots) };
+        let a = pool.allocate().unwrap();
+        let b = pool.allocate().unwrap();
+        pool.deallocate(a).unwrap();
+        let c = pool.allocate().unwrap();
+        let d = pool.allocate().unwrap();
+        assert!(c == a && d != b, "Testing freed slots are reused without handing out a live one");
+        assert!(pool.allocate().is_err() && pool.len() == 3, "Testing the pool is full");
+    }
+    #[test]
+    fn iter_test() {
+        let mut slots = [0u64; 8];
+        let mut pool = unsafe { Pool::from_slice(&mut slots) };
+        let allocations: Vec<_> = (0..5).map(|i| {
+            let ptr = pool.allocate().unwrap();
+            unsafe { ptr.write(i) };
+            ptr
+        }).collect();
+        pool.deallocate(allocations[1]).unwrap();
+        pool.deallocate(allocations[3]).unwrap();
+        assert!(pool.len() == 3 && pool.is_live(allocations[0]) && !pool.is_live(allocations[1]), "Testing occupancy is tracked");
+        assert!(unsafe { pool.iter() }.copied().eq([0, 2, 4]), "Testing only live values are iterated");
+        unsafe { pool.iter_mut() }.for_each(|value| *value *= 10);
+        assert!(unsafe { pool.iter() }.copied().eq([0, 20, 40]), "Testing live values can be mutated");
+    }
+    #[test]
+    fn drain_test() {
+        let dropped = Rc::new(Cell::new(0));
+        struct Counted(Rc<Cell<usize>>);
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        let arena = StandardArena::new(1024);
+        let mut pool = Pool::<Counted>::from_arena(&arena, Layout::new::<[Counted; 8]>()).unwrap();
+        for _ in 0..4 {
+            unsafe { pool.allocate().unwrap().write(Counted(dropped.clone())) };
+        }
+        let first = unsafe { pool.drain() }.next();
+        assert!(first.is_some() && dropped.get() == 3, "Testing values the drain didn't reach are dropped");
+        drop(first);
+        assert!(dropped.get() == 4 && pool.is_empty(), "Testing draining resets the pool");
+        assert!(pool.allocate().unwrap().as_ptr() as *mut u8 == pool.as_ptr(), "Testing the pool starts over after draining");
+    }
 }
\ No newline at end of file
@@ -0,0 +1,191 @@
+use std::{alloc::{Allocator, Layout}, collections::HashMap, fmt::Display, io::{BufRead, Write}, ptr::NonNull, str::FromStr, sync::Mutex};
+
+use crate::error::TraceError;
+
+/// A single event of an allocation trace.
+/// # Format
+/// Traces are plain text with one event per line, empty lines and lines starting with `#` are ignored.
+/// ```text
+/// a <id> <size> <align>   allocate
+/// r <id> <size>           reallocate, keeping the alignment
+/// f <id>                  free
+/// ```
+/// `id` identifies an allocation for the rest of the trace and may be reused once it's freed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    Alloc { id: u64, size: usize, align: usize },
+    Realloc { id: u64, size: usize },
+    Free { id: u64 },
+}
+
+impl Display for TraceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceEvent::Alloc { id, size, align } => write!(f, "a {id} {size} {align}"),
+            TraceEvent::Realloc { id, size } => write!(f, "r {id} {size}"),
+            TraceEvent::Free { id } => write!(f, "f {id}"),
+        }
+    }
+}
+
+impl FromStr for TraceEvent {
+    type Err = &'static str;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split_whitespace();
+        let kind = parts.next().ok_or("empty event")?;
+        let mut number = || -> Result<u64, &'static str> {
+            parts.next().ok_or("missing field")?.parse().map_err(|_| "invalid number")
+        };
+        let event = match kind {
+            "a" => {
+                let (id, size, align) = (number()?, number()? as usize, number()? as usize);
+                if !align.is_power_of_two() {
+                    return Err("alignment must be a power of two");
+                }
+                TraceEvent::Alloc { id, size, align }
+            }
+            "r" => TraceEvent::Realloc { id: number()?, size: number()? as usize },
+            "f" => TraceEvent::Free { id: number()? },
+            _ => return Err("unknown event kind"),
+        };
+        if parts.next().is_some() {
+            return Err("too many fields");
+        }
+        Ok(event)
+    }
+}
+
+/// Reads every event of a trace.
+pub fn read_trace(reader: impl BufRead) -> Result<Vec<TraceEvent>, TraceError> {
+    let mut events = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let event = line.parse().map_err(|reason| TraceError::InvalidEvent { line: index + 1, reason })?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+struct RecordingState<W: Write> {
+    writer: W,
+    next_id: u64,
+    /// ids of the live allocations by address.
+    ids: HashMap<usize, u64>,
+}
+
+/// Wrapper that writes every allocation made through it as a trace, see [`TraceEvent`]
+/// for the format. The trace can be replayed against other allocators with the `nightfall` binary.
+///
+/// Write errors are ignored, since they can't be reported through [`Allocator`].
+pub struct RecordingAllocator<A, W: Write> {
+    inner: A,
+    state: Mutex<RecordingState<W>>,
+}
+
+impl<A, W: Write> RecordingAllocator<A, W> {
+    pub fn new(inner: A, writer: W) -> Self {
+        Self { inner, state: Mutex::new(RecordingState { writer, next_id: 0, ids: HashMap::new() }) }
+    }
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+    /// flushes the trace written so far.
+    pub fn flush(&self) -> std::io::Result<()> {
+        self.state.lock().unwrap().writer.flush()
+    }
+    /// stops recording and returns the writer.
+    pub fn into_writer(self) -> W {
+        self.state.into_inner().unwrap().writer
+    }
+    fn record_alloc(&self, ptr: NonNull<[u8]>, layout: Layout) {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.ids.insert(ptr.as_ptr().cast::<u8>() as usize, id);
+        let _ = writeln!(state.writer, "{}", TraceEvent::Alloc { id, size: layout.size(), align: layout.align() });
+    }
+    fn record_realloc(&self, old: NonNull<u8>, new: NonNull<[u8]>, layout: Layout) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(id) = state.ids.remove(&(old.as_ptr() as usize)) {
+            state.ids.insert(new.as_ptr().cast::<u8>() as usize, id);
+            let _ = writeln!(state.writer, "{}", TraceEvent::Realloc { id, size: layout.size() });
+        }
+    }
+    fn record_free(&self, ptr: NonNull<u8>) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(id) = state.ids.remove(&(ptr.as_ptr() as usize)) {
+            let _ = writeln!(state.writer, "{}", TraceEvent::Free { id });
+        }
+    }
+}
+
+unsafe impl<A: Allocator, W: Write> Allocator for RecordingAllocator<A, W> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let ptr = self.inner.allocate(layout)?;
+        self.record_alloc(ptr, layout);
+        Ok(ptr)
+    }
+    fn allocate_zeroed(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let ptr = self.inner.allocate_zeroed(layout)?;
+        self.record_alloc(ptr, layout);
+        Ok(ptr)
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        self.record_free(ptr);
+        unsafe { self.inner.deallocate(ptr, layout) }
+    }
+    unsafe fn grow(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let new = unsafe { self.inner.grow(ptr, old_layout, new_layout)? };
+        self.record_realloc(ptr, new, new_layout);
+        Ok(new)
+    }
+    unsafe fn grow_zeroed(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let new = unsafe { self.inner.grow_zeroed(ptr, old_layout, new_layout)? };
+        self.record_realloc(ptr, new, new_layout);
+        Ok(new)
+    }
+    unsafe fn shrink(&self, ptr: NonNull<u8>, old_layout: Layout, new_layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        let new = unsafe { self.inner.shrink(ptr, old_layout, new_layout)? };
+        self.record_realloc(ptr, new, new_layout);
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::alloc::Global;
+
+    use super::{read_trace, RecordingAllocator, TraceEvent};
+    #[test]
+    fn recording_test() {
+        let recording = RecordingAllocator::new(Global, Vec::new());
+        {
+            let mut vector = Vec::<u32, _>::with_capacity_in(4, &recording);
+            vector.extend(0..8);
+            let _boxed = Box::new_in(7u64, &recording);
+        }
+        let trace = recording.into_writer();
+        let events = read_trace(trace.as_slice()).unwrap();
+        assert!(events == [
+            TraceEvent::Alloc { id: 0, size: 16, align: 4 },
+            TraceEvent::Realloc { id: 0, size: 32 },
+            TraceEvent::Alloc { id: 1, size: 8, align: 8 },
+            TraceEvent::Free { id: 1 },
+            TraceEvent::Free { id: 0 },
+        ], "Testing the trace was recorded correctly: {events:?}");
+    }
+    #[test]
+    fn parse_test() {
+        let trace = "# comment\n\na 3 24 8\nr 3 48\nf 3\n";
+        let events = read_trace(trace.as_bytes()).unwrap();
+        assert!(events.len() == 3 && events[0] == TraceEvent::Alloc { id: 3, size: 24, align: 8 }, "Testing the trace was parsed");
+        let written: Vec<_> = events.iter().map(|event| event.to_string()).collect();
+        assert!(written == ["a 3 24 8", "r 3 48", "f 3"], "Testing events are written back the same way");
+        let error = read_trace("a 1 8 3\n".as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("line 1"), "Testing invalid events are reported");
+    }
+}
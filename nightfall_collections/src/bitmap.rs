@@ -29,4 +29,182 @@ impl Bitmap {
         let bit = coords%u64::BITS;
         self.buf[idx] & (1 << bit) != 0
     }
+}
+
+/// One dimensional [`Bitmap`], a fixed amount of bits addressed by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitSet {
+    len: usize,
+    buf: Vec<u64>,
+}
+
+impl BitSet {
+    pub fn new(len: usize) -> Self {
+        Self { len, buf: vec![0; len.div_ceil(u64::BITS as usize)] }
+    }
+    /// amount of bits in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    pub fn flip(&mut self, index: usize, flip: bool) {
+        assert!(index < self.len, "index out of bounds for BitSet");
+        let bit = index%u64::BITS as usize;
+        if flip {
+            self.buf[index.div(u64::BITS as usize)] |= 1 << bit;
+        } else {
+            self.buf[index.div(u64::BITS as usize)] &= !(1 << bit);
+        }
+    }
+    pub fn active(&self, index: usize) -> bool {
+        assert!(index < self.len, "index out of bounds for BitSet");
+        self.buf[index.div(u64::BITS as usize)] & (1 << (index%u64::BITS as usize)) != 0
+    }
+    /// amount of active bits.
+    pub fn count(&self) -> usize {
+        self.buf.iter().map(|word| word.count_ones() as usize).sum()
+    }
+    /// deactivates every bit.
+    pub fn clear(&mut self) {
+        self.buf.fill(0);
+    }
+    /// sets every bit in `start..end` to `flip`, a word at a time.
+    pub fn flip_range(&mut self, start: usize, end: usize, flip: bool) {
+        assert!(start <= end && end <= self.len, "range out of bounds for BitSet");
+        let mut index = start;
+        while index < end {
+            let bit = index%u64::BITS as usize;
+            let bits = (end - index).min(u64::BITS as usize - bit);
+            let mask = if bits == u64::BITS as usize { !0 } else { ((1u64 << bits) - 1) << bit };
+            if flip {
+                self.buf[index.div(u64::BITS as usize)] |= mask;
+            } else {
+                self.buf[index.div(u64::BITS as usize)] &= !mask;
+            }
+            index += bits;
+        }
+    }
+    /// index of the first inactive bit at or after `from`. Full words are skipped
+    /// without looking at their bits.
+    pub fn first_zero(&self, from: usize) -> Option<usize> {
+        if from >= self.len {
+            return None;
+        }
+        let word = from.div(u64::BITS as usize);
+        let first = !self.buf[word] & (!0u64 << (from%u64::BITS as usize));
+        let index = if first != 0 {
+            word*u64::BITS as usize + first.trailing_zeros() as usize
+        } else {
+            let offset = self.buf[word+1..].iter().position(|&bits| bits != !0)?;
+            (word + 1 + offset)*u64::BITS as usize + self.buf[word + 1 + offset].trailing_ones() as usize
+        };
+        (index < self.len).then_some(index)
+    }
+    /// index of the first active bit in `start..end`.
+    pub fn first_one(&self, start: usize, end: usize) -> Option<usize> {
+        let end = end.min(self.len);
+        let mut index = start;
+        while index < end {
+            let bit = index%u64::BITS as usize;
+            let bits = self.buf[index.div(u64::BITS as usize)] >> bit;
+            if bits != 0 {
+                let found = index + bits.trailing_zeros() as usize;
+                return (found < end).then_some(found);
+            }
+            index += u64::BITS as usize - bit;
+        }
+        None
+    }
+    /// iterates over the indices of every active bit in ascending order.
+    pub fn ones(&self) -> Ones<'_> {
+        Ones { buf: &self.buf, word: 0, current: self.buf.first().copied().unwrap_or(0) }
+    }
+}
+
+/// Iterator over the active bits of a [`BitSet`], see [`BitSet::ones`].
+#[derive(Debug, Clone)]
+pub struct Ones<'a> {
+    buf: &'a [u64],
+    word: usize,
+    /// bits of the current word that haven't been returned yet.
+    current: u64,
+}
+
+impl Iterator for Ones<'_> {
+    type Item = usize;
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.current == 0 {
+            self.word += 1;
+            self.current = *self.buf.get(self.word)?;
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        self.current &= self.current - 1;
+        Some(self.word*u64::BITS as usize + bit)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::BitSet;
+
+    /// checks every query of `set` against the same bits kept as a `Vec<bool>`.
+    fn assert_matches(set: &BitSet, model: &[bool]) {
+        let ones: Vec<_> = (0..model.len()).filter(|&i| model[i]).collect();
+        assert!(set.ones().eq(ones.iter().copied()), "Testing ones yields the active bits in order");
+        assert!(set.count() == ones.len(), "Testing count");
+        for start in 0..=model.len() {
+            let zero = (start..model.len()).find(|&i| !model[i]);
+            assert!(set.first_zero(start) == zero, "Testing first_zero from {start}");
+            for end in [start, start + 1, start + 63, start + 64, start + 65, model.len(), model.len() + 1] {
+                let one = (start..end.min(model.len())).find(|&i| model[i]);
+                assert!(set.first_one(start, end) == one, "Testing first_one in {start}..{end}");
+            }
+        }
+    }
+    #[test]
+    fn boundary_test() {
+        for len in [0, 1, 63, 64, 65, 127, 128, 130] {
+            let mut set = BitSet::new(len);
+            let mut model = vec![false; len];
+            assert_matches(&set, &model);
+            // ranges that start, end or cross at the word boundaries
+            for (start, end) in [(0, 63), (63, 64), (64, 65), (62, 66), (1, 130), (0, 130)] {
+                let (start, end) = (start.min(len), end.min(len));
+                for flip in [true, false, true] {
+                    set.flip_range(start, end, flip);
+                    model[start..end].fill(flip);
+                    assert_matches(&set, &model);
+                }
+            }
+            // the set is full now
+            assert!(set.count() == len && set.first_zero(0).is_none(), "Testing a full set of {len} bits");
+            set.clear();
+            model.fill(false);
+            assert_matches(&set, &model);
+            assert!(set.first_one(0, len).is_none() && set.ones().next().is_none(), "Testing an empty set of {len} bits");
+        }
+    }
+    #[test]
+    fn random_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let len = 200;
+        let mut set = BitSet::new(len);
+        let mut model = vec![false; len];
+        for _ in 0..200 {
+            let start = rng.random_range(0..=len);
+            let end = rng.random_range(start..=len);
+            let flip = rng.random_bool(0.5);
+            set.flip_range(start, end, flip);
+            model[start..end].fill(flip);
+            let index = rng.random_range(0..len);
+            set.flip(index, !model[index]);
+            model[index] = !model[index];
+            assert!((0..len).all(|i| set.active(i) == model[i]), "Testing flip_range and flip set the right bits");
+        }
+        assert_matches(&set, &model);
+    }
 }
\ No newline at end of file
@@ -1,7 +1,5 @@
 use std::{alloc::Layout, ptr::NonNull};
 
-use anyhow::Ok;
-
 use crate::error::CollectionError;
 /// Fixed size circular buffer 
 #[derive(Debug, Clone)]
@@ -39,10 +37,11 @@ impl<T> CircularBuffer<T> {
         self.len
     }
 
-    pub fn push(&mut self, value: T) -> anyhow::Result<()> {
+    /// Pushes `value` to the back of the buffer, giving it back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), CollectionError<T>> {
         let capacity = self.capacity();
         if self.len == capacity {
-            Err(CollectionError::CapacityFull)?;
+            return Err(CollectionError::CapacityFull(value));
         }
         unsafe {
             let end = self.buf.as_ptr().add(self.head);
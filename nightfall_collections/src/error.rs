@@ -1,7 +1,11 @@
 use thiserror::Error;
 
-#[derive(Debug, Error)]
-pub enum CollectionError {
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum CollectionError<T> {
+    /// hands back the value that couldn't be inserted.
     #[error("Not enough capacity to insert elements")]
-    CapacityFull
+    CapacityFull(T),
+    /// hands back the entry that would have broken the order of the collection.
+    #[error("Inserting here would break the order of the keys")]
+    OutOfOrder(T),
 }
\ No newline at end of file
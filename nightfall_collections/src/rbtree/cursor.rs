@@ -0,0 +1,307 @@
+use std::alloc::{Allocator, Global};
+
+use crate::error::CollectionError;
+
+use super::{NodePtr, RBTree};
+
+impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
+    fn first_node(&self) -> NodePtr<K, T> {
+        if self.root.is_null() { NodePtr::null() } else { Self::minimum_node(self.root) }
+    }
+    fn last_node(&self) -> NodePtr<K, T> {
+        if self.root.is_null() { NodePtr::null() } else { Self::maximum_node(self.root) }
+    }
+    /// node after `node`, the ghost position comes before the first node.
+    fn next_of(&self, node: NodePtr<K, T>) -> NodePtr<K, T> {
+        if node.is_null() { self.first_node() } else { node.successor() }
+    }
+    /// node before `node`, the ghost position comes after the last node.
+    fn prev_of(&self, node: NodePtr<K, T>) -> NodePtr<K, T> {
+        if node.is_null() { self.last_node() } else { node.predecessor() }
+    }
+}
+
+/// Position in a [`super::RBTreeMap`] that can move between neighbouring entries.
+/// # Concepts
+/// A cursor points at an entry or at the ghost position, which links the last entry back to
+/// the first one. Moving follows the links of the nodes rather than searching from the root,
+/// so sweeping over a map with a cursor costs the same as iterating it.
+/// ```text
+/// ghost ⇄ first ⇄ ... ⇄ last ⇄ ghost
+/// ```
+pub struct Cursor<'a, K: Ord, T, A: Allocator = Global> {
+    tree: &'a RBTree<K, T, A>,
+    current: NodePtr<K, T>,
+}
+
+impl<'a, K: Ord, T, A: Allocator> Cursor<'a, K, T, A> {
+    pub(crate) fn new(tree: &'a RBTree<K, T, A>, current: NodePtr<K, T>) -> Self {
+        Self { tree, current }
+    }
+    /// whether the cursor is at the ghost position.
+    pub fn is_ghost(&self) -> bool {
+        self.current.is_null()
+    }
+    pub fn key(&self) -> Option<&'a K> {
+        self.key_value().map(|(key, _)| key)
+    }
+    pub fn value(&self) -> Option<&'a T> {
+        self.key_value().map(|(_, value)| value)
+    }
+    pub fn key_value(&self) -> Option<(&'a K, &'a T)> {
+        if self.current.is_null() {
+            return None;
+        }
+        unsafe { Some((&(*self.current.0).key, &(*self.current.0).value)) }
+    }
+    pub fn move_next(&mut self) {
+        self.current = self.tree.next_of(self.current);
+    }
+    pub fn move_prev(&mut self) {
+        self.current = self.tree.prev_of(self.current);
+    }
+    pub fn peek_next(&self) -> Option<(&'a K, &'a T)> {
+        Self::new(self.tree, self.tree.next_of(self.current)).key_value()
+    }
+    pub fn peek_prev(&self) -> Option<(&'a K, &'a T)> {
+        Self::new(self.tree, self.tree.prev_of(self.current)).key_value()
+    }
+}
+
+impl<K: Ord, T, A: Allocator> Clone for Cursor<'_, K, T, A> {
+    fn clone(&self) -> Self {
+        Self { tree: self.tree, current: self.current }
+    }
+}
+
+/// [`Cursor`] that can also change the map around it. Inserting next to the cursor links the
+/// node in place, as long as the key keeps the map in order.
+pub struct CursorMut<'a, K: Ord, T, A: Allocator = Global> {
+    tree: &'a mut RBTree<K, T, A>,
+    current: NodePtr<K, T>,
+}
+
+impl<'a, K: Ord, T, A: Allocator> CursorMut<'a, K, T, A> {
+    pub(crate) fn new(tree: &'a mut RBTree<K, T, A>, current: NodePtr<K, T>) -> Self {
+        Self { tree, current }
+    }
+    pub fn as_cursor(&self) -> Cursor<'_, K, T, A> {
+        Cursor::new(self.tree, self.current)
+    }
+    pub fn is_ghost(&self) -> bool {
+        self.current.is_null()
+    }
+    pub fn key(&self) -> Option<&K> {
+        self.as_cursor().key()
+    }
+    pub fn value(&self) -> Option<&T> {
+        self.as_cursor().value()
+    }
+    pub fn value_mut(&mut self) -> Option<&mut T> {
+        if self.current.is_null() {
+            return None;
+        }
+        Some(self.current.value_mut())
+    }
+    pub fn key_value(&self) -> Option<(&K, &T)> {
+        self.as_cursor().key_value()
+    }
+    pub fn move_next(&mut self) {
+        self.current = self.tree.next_of(self.current);
+    }
+    pub fn move_prev(&mut self) {
+        self.current = self.tree.prev_of(self.current);
+    }
+    pub fn peek_next(&self) -> Option<(&K, &T)> {
+        self.as_cursor().peek_next()
+    }
+    pub fn peek_prev(&self) -> Option<(&K, &T)> {
+        self.as_cursor().peek_prev()
+    }
+    /// Removes the current entry and moves to the next one. Does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<(K, T)> {
+        if self.current.is_null() {
+            return None;
+        }
+        let next = self.current.successor();
+        let entry = self.tree.delete_entry(self.current);
+        self.current = next;
+        entry
+    }
+    /// Inserts an entry right before the current one, or as the last one at the ghost position.
+    /// The cursor doesn't move.
+    pub fn insert_before(&mut self, key: K, value: T) -> Result<(), CollectionError<(K, T)>> {
+        let prev = self.tree.prev_of(self.current);
+        let after_prev = prev.is_null() || prev.key() < &key;
+        let before_current = self.current.is_null() || &key < self.current.key();
+        if !after_prev || !before_current {
+            return Err(CollectionError::OutOfOrder((key, value)));
+        }
+        // the predecessor of a node with a left child has no right child
+        let (parent, left) = if self.current.is_null() || !self.current.left().is_null() {
+            (prev, false)
+        } else {
+            (self.current, true)
+        };
+        self.tree.insert_at(parent, left, key, value);
+        Ok(())
+    }
+    /// Inserts an entry right after the current one, or as the first one at the ghost position.
+    /// The cursor doesn't move.
+    pub fn insert_after(&mut self, key: K, value: T) -> Result<(), CollectionError<(K, T)>> {
+        let next = self.tree.next_of(self.current);
+        let before_next = next.is_null() || &key < next.key();
+        let after_current = self.current.is_null() || self.current.key() < &key;
+        if !before_next || !after_current {
+            return Err(CollectionError::OutOfOrder((key, value)));
+        }
+        let (parent, left) = if self.current.is_null() || !self.current.right().is_null() {
+            (next, true)
+        } else {
+            (self.current, false)
+        };
+        self.tree.insert_at(parent, left, key, value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Bound;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::{error::CollectionError, rbtree::{test::assert_valid, RBTreeMap}};
+
+    fn keys(map: &RBTreeMap<i32, i32>) -> Vec<i32> {
+        map.iter().map(|(&key, _)| key).collect()
+    }
+    #[test]
+    fn bounds_test() {
+        let mut map = RBTreeMap::new();
+        assert!(map.cursor_front().is_ghost() && map.cursor_back().is_ghost(), "Testing an empty map only has the ghost");
+        assert!(map.cursor_front_mut().is_ghost() && map.cursor_back_mut().is_ghost(), "Testing an empty map only has the ghost");
+        for bound in [Bound::Included(&0), Bound::Excluded(&0), Bound::Unbounded] {
+            assert!(map.lower_bound(bound).is_ghost() && map.lower_bound_mut(bound).is_ghost(), "Testing lower_bound on an empty map");
+            assert!(map.upper_bound(bound).is_ghost() && map.upper_bound_mut(bound).is_ghost(), "Testing upper_bound on an empty map");
+        }
+        for key in [0, 10, 20, 30] {
+            map.insert(key, key*10);
+        }
+        assert!(map.cursor_front().key_value() == Some((&0, &0)) && map.cursor_front_mut().key() == Some(&0), "Testing cursor_front");
+        assert!(map.cursor_back().key_value() == Some((&30, &300)) && map.cursor_back_mut().key() == Some(&30), "Testing cursor_back");
+        let lower = [
+            (Bound::Included(&10), Some(10)),
+            (Bound::Excluded(&10), Some(20)),
+            (Bound::Included(&15), Some(20)),
+            (Bound::Excluded(&-5), Some(0)),
+            (Bound::Unbounded, Some(0)),
+            (Bound::Included(&31), None),
+            (Bound::Excluded(&30), None),
+        ];
+        for (bound, key) in lower {
+            assert!(map.lower_bound(bound).key().copied() == key, "Testing lower_bound({bound:?})");
+            assert!(map.lower_bound_mut(bound).key().copied() == key, "Testing lower_bound_mut({bound:?})");
+        }
+        let upper = [
+            (Bound::Included(&20), Some(20)),
+            (Bound::Excluded(&20), Some(10)),
+            (Bound::Included(&25), Some(20)),
+            (Bound::Excluded(&35), Some(30)),
+            (Bound::Unbounded, Some(30)),
+            (Bound::Included(&-1), None),
+            (Bound::Excluded(&0), None),
+        ];
+        for (bound, key) in upper {
+            assert!(map.upper_bound(bound).key().copied() == key, "Testing upper_bound({bound:?})");
+            assert!(map.upper_bound_mut(bound).key().copied() == key, "Testing upper_bound_mut({bound:?})");
+        }
+        let ghost = map.lower_bound(Bound::Excluded(&30));
+        assert!(ghost.peek_next() == Some((&0, &0)) && ghost.peek_prev() == Some((&30, &300)), "Testing the ghost sits between the last and the first entry");
+    }
+    #[test]
+    fn insert_test() {
+        let mut map = RBTreeMap::new();
+        let mut cursor = map.cursor_front_mut();
+        cursor.insert_after(5, 50).unwrap();
+        assert!(cursor.is_ghost() && cursor.peek_next() == Some((&5, &50)), "Testing inserting into an empty map");
+        cursor.insert_after(1, 10).unwrap();
+        cursor.insert_before(9, 90).unwrap();
+        assert!(cursor.peek_next() == Some((&1, &10)) && cursor.peek_prev() == Some((&9, &90)), "Testing the ghost inserts after as first and before as last");
+        assert!(cursor.insert_after(7, 70) == Err(CollectionError::OutOfOrder((7, 70))), "Testing the ghost only inserts after below the first key");
+        assert!(cursor.insert_before(7, 70) == Err(CollectionError::OutOfOrder((7, 70))), "Testing the ghost only inserts before above the last key");
+        let mut cursor = map.cursor_front_mut();
+        cursor.insert_before(0, 0).unwrap();
+        let mut cursor = map.cursor_back_mut();
+        cursor.insert_after(10, 100).unwrap();
+        assert!(cursor.key() == Some(&9), "Testing the cursor doesn't move on insertion");
+        assert_valid(&map.base);
+        assert!(keys(&map) == [0, 1, 5, 9, 10], "Testing inserting at both ends");
+        let mut cursor = map.lower_bound_mut(Bound::Included(&5));
+        cursor.insert_before(3, 30).unwrap();
+        cursor.insert_after(6, 60).unwrap();
+        cursor.insert_before(4, 40).unwrap();
+        assert!(cursor.insert_after(8, 80) == Err(CollectionError::OutOfOrder((8, 80))), "Testing keys above the next entry are handed back");
+        assert!(cursor.insert_before(2, 20) == Err(CollectionError::OutOfOrder((2, 20))), "Testing keys below the previous entry are handed back");
+        assert!(cursor.insert_after(5, 0) == Err(CollectionError::OutOfOrder((5, 0))), "Testing the current key is handed back");
+        assert!(cursor.peek_prev() == Some((&4, &40)) && cursor.peek_next() == Some((&6, &60)), "Testing inserting next to interior entries");
+        assert_valid(&map.base);
+        assert!(keys(&map) == [0, 1, 3, 4, 5, 6, 9, 10], "Testing the order after interior insertions");
+    }
+    #[test]
+    fn remove_test() {
+        let mut map = RBTreeMap::new();
+        for key in 0..4 {
+            map.insert(key, key*10);
+        }
+        let mut cursor = map.cursor_back_mut();
+        assert!(cursor.remove_current() == Some((3, 30)) && cursor.is_ghost(), "Testing removing the last entry moves to the ghost");
+        assert!(cursor.remove_current().is_none() && cursor.peek_next() == Some((&0, &0)), "Testing the ghost can't be removed");
+        let mut cursor = map.cursor_front_mut();
+        assert!(cursor.remove_current() == Some((0, 0)) && cursor.key() == Some(&1), "Testing removing moves to the next entry");
+        let mut cursor = map.upper_bound_mut(Bound::Excluded(&2));
+        assert!(cursor.value_mut().map(|value| std::mem::replace(value, 11)) == Some(10), "Testing editing through the cursor");
+        assert_valid(&map.base);
+        assert!(map.iter().map(|(&key, &value)| (key, value)).eq([(1, 11), (2, 20)]), "Testing the remaining entries");
+    }
+    #[test]
+    fn random_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut map = RBTreeMap::new();
+        let mut reference = Vec::new();
+        let mut position = 0;
+        for _ in 0..2000 {
+            // the cursor sits on `reference[position]`, or the ghost at `reference.len()`
+            let mut cursor = match reference.get(position) {
+                Some(key) => map.lower_bound_mut(Bound::Included(key)),
+                None => map.upper_bound_mut(Bound::Excluded(&i32::MIN)),
+            };
+            let key = rng.random_range(0..500);
+            match rng.random_range(0..4) {
+                0 => {
+                    let fits = reference.get(position).is_none_or(|&current| key < current) && (position == 0 || reference[position - 1] < key);
+                    assert!(cursor.insert_before(key, key).is_ok() == fits, "Testing insert_before");
+                    if fits {
+                        reference.insert(position, key);
+                        position += 1;
+                    }
+                }
+                1 => {
+                    let next = if position == reference.len() { 0 } else { position + 1 };
+                    let fits = (position == reference.len() || reference[position] < key) && reference.get(next).is_none_or(|&next| key < next);
+                    assert!(cursor.insert_after(key, key).is_ok() == fits, "Testing insert_after");
+                    if fits {
+                        reference.insert(next, key);
+                        position += usize::from(next <= position);
+                    }
+                }
+                2 if position < reference.len() => {
+                    assert!(cursor.remove_current().map(|(key, _)| key) == Some(reference.remove(position)), "Testing remove_current");
+                }
+                _ => position = rng.random_range(0..=reference.len()),
+            }
+            assert_valid(&map.base);
+            assert!(keys(&map) == reference, "Testing cursor edits match a sorted Vec");
+        }
+    }
+}
\ No newline at end of file
@@ -1,9 +1,9 @@
-use std::alloc::{Allocator, Global};
+use std::{alloc::{Allocator, Global}, borrow::Borrow, ops::{Bound, RangeBounds}};
 
-use super::{Iter, IterMut, Keys, RBTree, Values, ValuesMut};
+use super::{Cursor, CursorMut, Iter, IterMut, Keys, NodePtr, RBTree, Range, RangeMut, Values, ValuesMut};
 
 pub struct RBTreeMap<K: Ord, T, A: Allocator = Global> {
-    base: RBTree<K, T, A>,
+    pub(super) base: RBTree<K, T, A>,
 }
 impl<K: Ord, T> RBTreeMap<K, T> {
     pub fn new() -> Self {
@@ -14,27 +14,37 @@ impl<K: Ord, T, A: Allocator> RBTreeMap<K, T, A> {
     pub fn new_in(alloc: A) -> Self {
         Self { base: RBTree::new_in(alloc) }
     }
+    /// Inserts `value` under `key`, returning the value it replaced. The key already in the map is kept.
     pub fn insert(&mut self, key: K, value: T) -> Option<T> {
-        let node = self.base.find_node(&key);
-        if node.is_null() {
-            self.base.insert(key, value);
-            None
-        } else {
-            let get = unsafe { std::ptr::read(node.value()) };
-            node.node_mut().value = value;
-            Some(get)
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
         }
     }
-    pub fn get(&self, key: &K) -> Option<&T> {
+    /// Gets the entry of `key` to insert or update it, walking the tree only once.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, T, A> {
+        match self.base.search_insert_position(&key) {
+            Ok(node) => Entry::Occupied(OccupiedEntry { tree: &mut self.base, node }),
+            Err((parent, left)) => Entry::Vacant(VacantEntry { tree: &mut self.base, key, parent, left }),
+        }
+    }
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.get(key)
     }
-    pub fn get_key_value(&self, key: &K) -> Option<(&K, &T)> {
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &T)>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.get_key_value(key)
     }
-    pub fn get_mut(&mut self, key: &K) -> Option<&mut T> {
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.get_mut(key)
     }
-    pub fn remove(&mut self, key: &K) -> Option<T> {
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.remove(key)
     }
     pub fn iter(&self) -> Iter<'_, K, T, A> {
@@ -43,6 +53,51 @@ impl<K: Ord, T, A: Allocator> RBTreeMap<K, T, A> {
     pub fn iter_mut(&mut self) -> IterMut<'_, K, T, A> {
         self.base.iter_mut()
     }
+    /// Iterates over the entries whose keys are in `range`, in order and from either end.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        self.base.range(range)
+    }
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        self.base.range_mut(range)
+    }
+    /// Cursor at the first entry, or at the ghost position if the map is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, K, T, A> {
+        Cursor::new(&self.base, self.base.lower_bound_node::<K>(Bound::Unbounded))
+    }
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, K, T, A> {
+        let node = self.base.lower_bound_node::<K>(Bound::Unbounded);
+        CursorMut::new(&mut self.base, node)
+    }
+    /// Cursor at the last entry, or at the ghost position if the map is empty.
+    pub fn cursor_back(&self) -> Cursor<'_, K, T, A> {
+        Cursor::new(&self.base, self.base.upper_bound_node::<K>(Bound::Unbounded))
+    }
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, K, T, A> {
+        let node = self.base.upper_bound_node::<K>(Bound::Unbounded);
+        CursorMut::new(&mut self.base, node)
+    }
+    /// Cursor at the first entry above `bound`, or at the ghost position if there's none.
+    pub fn lower_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, T, A>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        Cursor::new(&self.base, self.base.lower_bound_node(bound))
+    }
+    pub fn lower_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, T, A>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let node = self.base.lower_bound_node(bound);
+        CursorMut::new(&mut self.base, node)
+    }
+    /// Cursor at the last entry below `bound`, or at the ghost position if there's none.
+    pub fn upper_bound<Q>(&self, bound: Bound<&Q>) -> Cursor<'_, K, T, A>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        Cursor::new(&self.base, self.base.upper_bound_node(bound))
+    }
+    pub fn upper_bound_mut<Q>(&mut self, bound: Bound<&Q>) -> CursorMut<'_, K, T, A>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let node = self.base.upper_bound_node(bound);
+        CursorMut::new(&mut self.base, node)
+    }
     pub fn values(&self) -> Values<K, T, A> {
         Values { iter: self.iter() }
     }
@@ -58,17 +113,241 @@ impl<K: Ord, T, A: Allocator> RBTreeMap<K, T, A> {
     pub fn clear(&mut self) {
         self.base.clear();
     }
-    pub(crate) fn find_node(&self, key: &K) -> super::NodePtr<K, T> {
+    pub(crate) fn find_node<Q>(&self, key: &Q) -> super::NodePtr<K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.find_node(key)
     }
     pub fn is_clear(&self) -> bool {
         self.base.is_clear()
     }
-    pub fn contains_key(&self, key: &K) -> bool {
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.get(key).is_some()
     }
     #[inline]
     pub fn len(&self) -> usize {
         self.base.len()
     }
+    /// Amount of keys smaller than `key`, which is the position `key` has or would have in order.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.base.rank(key)
+    }
+    /// Entry at position `index` in order, in O(log n).
+    pub fn get_index(&self, index: usize) -> Option<(&K, &T)> {
+        self.base.select(index)
+    }
+}
+
+/// Entry of a key in a [`RBTreeMap`], see [`RBTreeMap::entry`].
+pub enum Entry<'a, K: Ord, T, A: Allocator = Global> {
+    Occupied(OccupiedEntry<'a, K, T, A>),
+    Vacant(VacantEntry<'a, K, T, A>),
+}
+
+impl<'a, K: Ord, T, A: Allocator> Entry<'a, K, T, A> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+    pub fn or_insert(self, value: T) -> &'a mut T {
+        self.or_insert_with(|| value)
+    }
+    pub fn or_insert_with<F: FnOnce() -> T>(self, f: F) -> &'a mut T {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+    pub fn or_default(self) -> &'a mut T
+        where T: Default {
+        self.or_insert_with(T::default)
+    }
+    /// Runs `f` on the value if the key is in the map.
+    pub fn and_modify<F: FnOnce(&mut T)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, K: Ord, T, A: Allocator = Global> {
+    tree: &'a mut RBTree<K, T, A>,
+    node: NodePtr<K, T>,
+}
+
+impl<'a, K: Ord, T, A: Allocator> OccupiedEntry<'a, K, T, A> {
+    pub fn key(&self) -> &K {
+        self.node.key()
+    }
+    pub fn get(&self) -> &T {
+        self.node.value()
+    }
+    pub fn get_mut(&mut self) -> &mut T {
+        self.node.value_mut()
+    }
+    pub fn into_mut(self) -> &'a mut T {
+        unsafe { &mut (*self.node.0).value }
+    }
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: T) -> T {
+        std::mem::replace(self.get_mut(), value)
+    }
+    pub fn remove(self) -> T {
+        self.tree.delete(self.node).unwrap()
+    }
+}
+
+pub struct VacantEntry<'a, K: Ord, T, A: Allocator = Global> {
+    tree: &'a mut RBTree<K, T, A>,
+    key: K,
+    /// where the search for the key ended, a node for it goes right under it.
+    parent: NodePtr<K, T>,
+    left: bool,
+}
+
+impl<'a, K: Ord, T, A: Allocator> VacantEntry<'a, K, T, A> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+    pub fn into_key(self) -> K {
+        self.key
+    }
+    pub fn insert(self, value: T) -> &'a mut T {
+        let node = self.tree.insert_at(self.parent, self.left, self.key, value);
+        unsafe { &mut (*node.0).value }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeMap, ops::Bound};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::rbtree::test::assert_valid;
+
+    use super::{Entry, RBTreeMap};
+
+    fn random_bound(rng: &mut StdRng) -> Bound<i32> {
+        match rng.random_range(0..3) {
+            0 => Bound::Included(rng.random_range(-2..66)),
+            1 => Bound::Excluded(rng.random_range(-2..66)),
+            _ => Bound::Unbounded,
+        }
+    }
+    /// whether `BTreeMap::range` accepts the bounds, it panics on inverted ones.
+    fn is_valid(start: Bound<i32>, end: Bound<i32>) -> bool {
+        match (start, end) {
+            (Bound::Excluded(start), Bound::Excluded(end)) => start < end,
+            (Bound::Included(start) | Bound::Excluded(start), Bound::Included(end) | Bound::Excluded(end)) => start <= end,
+            _ => true,
+        }
+    }
+    #[test]
+    fn range_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            let mut map = RBTreeMap::new();
+            let mut reference = BTreeMap::new();
+            for _ in 0..rng.random_range(0..48) {
+                let key = rng.random_range(0..64);
+                map.insert(key, key*10);
+                reference.insert(key, key*10);
+            }
+            for _ in 0..32 {
+                let bounds = (random_bound(&mut rng), random_bound(&mut rng));
+                if !is_valid(bounds.0, bounds.1) {
+                    assert!(map.range(bounds).next().is_none(), "Testing inverted ranges are empty");
+                    continue;
+                }
+                assert!(map.range(bounds).eq(reference.range(bounds)), "Testing range {bounds:?}");
+                assert!(map.range(bounds).rev().eq(reference.range(bounds).rev()), "Testing range {bounds:?} backwards");
+                // both ends meet somewhere in the middle
+                let mut range = map.range(bounds);
+                let mut expected = reference.range(bounds);
+                loop {
+                    let (item, expected_item) = if rng.random_bool(0.5) {
+                        (range.next(), expected.next())
+                    } else {
+                        (range.next_back(), expected.next_back())
+                    };
+                    assert!(item == expected_item, "Testing mixed ends of range {bounds:?}");
+                    if item.is_none() {
+                        break;
+                    }
+                }
+                assert!(range.next().is_none() && range.next_back().is_none(), "Testing exhausted ranges stay empty");
+                for (key, value) in map.range_mut(bounds) {
+                    *value += key;
+                }
+                for (key, value) in reference.range_mut(bounds) {
+                    *value += key;
+                }
+                assert!(map.iter().eq(reference.iter()), "Testing range_mut {bounds:?}");
+            }
+        }
+        let mut map = RBTreeMap::new();
+        for key in 0..10 {
+            map.insert(key, ());
+        }
+        assert!(map.range(4..4).next().is_none() && map.range(20..).next().is_none(), "Testing empty ranges");
+        assert!(map.range((Bound::Excluded(3), Bound::Excluded(4))).next().is_none(), "Testing ranges between neighbours");
+    }
+    #[test]
+    fn entry_test() {
+        let mut map = RBTreeMap::new();
+        for key in 0..32 {
+            *map.entry(key % 8).or_insert(0) += 1;
+            assert_valid(&map.base);
+        }
+        assert!(map.len() == 8 && map.iter().all(|(_, &count)| count == 4), "Testing or_insert inserts once and updates after");
+        let mut calls = 0;
+        for key in 4..12 {
+            map.entry(key).or_insert_with(|| { calls += 1; 100 });
+            assert_valid(&map.base);
+        }
+        assert!(calls == 4 && map.get(&10) == Some(&100) && map.get(&5) == Some(&4), "Testing or_insert_with only runs for vacant keys");
+        map.entry(3).and_modify(|value| *value *= 10).or_insert(0);
+        map.entry(20).and_modify(|value| *value *= 10).or_insert(7);
+        assert!(map.get(&3) == Some(&40) && map.get(&20) == Some(&7), "Testing and_modify only runs for occupied keys");
+        assert_valid(&map.base);
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut reference = BTreeMap::from_iter(map.iter().map(|(&key, &value)| (key, value)));
+        for _ in 0..2000 {
+            let key = rng.random_range(0..64);
+            match (map.entry(key), rng.random_bool(0.5)) {
+                (Entry::Occupied(entry), true) => assert!(entry.remove() == reference.remove(&key).unwrap(), "Testing OccupiedEntry::remove returns the value"),
+                (Entry::Occupied(mut entry), false) => assert!(entry.insert(key) == reference.insert(key, key).unwrap(), "Testing OccupiedEntry::insert replaces the value"),
+                (Entry::Vacant(entry), _) => {
+                    assert!(*entry.key() == key && *entry.insert(key) == key, "Testing VacantEntry::insert");
+                    reference.insert(key, key);
+                }
+            }
+            assert_valid(&map.base);
+            assert!(map.iter().eq(reference.iter()), "Testing entries match BTreeMap");
+        }
+    }
+    #[test]
+    fn borrow_test() {
+        let mut map = RBTreeMap::new();
+        for (index, key) in ["pear", "apple", "fig", "banana", "cherry"].into_iter().enumerate() {
+            map.insert(key.to_string(), index);
+        }
+        assert!(map.get("fig") == Some(&2) && map.get("kiwi").is_none(), "Testing get with &str");
+        assert!(map.get_key_value("pear").is_some_and(|(key, _)| key == "pear"), "Testing get_key_value with &str");
+        *map.get_mut("apple").unwrap() += 10;
+        assert!(map.get("apple") == Some(&11), "Testing get_mut with &str");
+        assert!(map.contains_key("banana") && !map.contains_key("banan"), "Testing contains_key with &str");
+        let range: Vec<_> = map.range::<str, _>((Bound::Included("b"), Bound::Excluded("fig"))).map(|(key, _)| key.as_str()).collect();
+        assert!(range == ["banana", "cherry"], "Testing range with &str bounds: {range:?}");
+        assert!(map.rank("cherry") == 2 && map.lower_bound(Bound::Excluded("cherry")).key().is_some_and(|key| key == "fig"), "Testing ordered lookups with &str");
+        assert!(map.remove("pear") == Some(0) && map.remove("pear").is_none() && map.len() == 4, "Testing remove with &str");
+        let mut bytes = RBTreeMap::new();
+        bytes.insert(vec![1u8, 2], ());
+        assert!(bytes.contains_key(&[1u8, 2][..]) && bytes.remove(&[1u8, 2][..]).is_some(), "Testing Vec keys looked up with slices");
+    }
 }
\ No newline at end of file
@@ -1,9 +1,12 @@
 #![allow(unused)]
-use std::{alloc::{Allocator, Global}, cmp::Ordering, fmt::Display, marker::PhantomData};
+use std::{alloc::{handle_alloc_error, Allocator, Global, Layout}, borrow::Borrow, cmp::Ordering, fmt::Display, iter::FusedIterator, marker::PhantomData, ops::{Bound, RangeBounds}};
+use crate::error::CollectionError;
 mod set;
 mod map;
+mod cursor;
 pub use set::*;
 pub use map::*;
+pub use cursor::*;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 enum Color {
     Red,
@@ -14,6 +17,8 @@ struct RBTreeNode<K: Ord, T> {
     left: NodePtr<K, T>,
     right: NodePtr<K, T>,
     parent: NodePtr<K, T>,
+    /// amount of nodes in the subtree rooted at this node, including itself.
+    size: usize,
     key: K,
     value: T
 }
@@ -58,20 +63,34 @@ impl<K: Ord, V> PartialEq for NodePtr<K, V> {
 impl<K: Ord, V> Eq for NodePtr<K, V> {}
 
 impl<K: Ord, T> NodePtr<K, T> {
-    fn new(k: K, v: T) -> NodePtr<K, T> {
+    fn new_in<A: Allocator>(k: K, v: T, alloc: &A) -> NodePtr<K, T> {
+        Self::try_new_in(k, v, alloc).unwrap_or_else(|_| handle_alloc_error(Layout::new::<RBTreeNode<K, T>>()))
+    }
+    /// hands `k` and `v` back if the node can't be allocated.
+    fn try_new_in<A: Allocator>(k: K, v: T, alloc: &A) -> Result<NodePtr<K, T>, (K, T)> {
+        let Ok(ptr) = alloc.allocate(Layout::new::<RBTreeNode<K, T>>()) else {
+            return Err((k, v));
+        };
+        let ptr = ptr.cast::<RBTreeNode<K, T>>().as_ptr();
         let node = RBTreeNode {
             color: Color::Red,
             left: NodePtr::null(),
             right: NodePtr::null(),
             parent: NodePtr::null(),
+            size: 1,
             key: k,
             value: v,
         };
-        NodePtr(Box::into_raw(Box::new(node)))
+        unsafe { ptr.write(node) };
+        Ok(NodePtr(ptr))
     }
     pub fn null() -> Self {
         Self(std::ptr::null_mut())
     }
+    /// takes the node back from the allocator it was created with, dropping the box frees it.
+    unsafe fn into_box<A: Allocator>(self, alloc: &A) -> Box<RBTreeNode<K, T>, &A> {
+        unsafe { Box::from_raw_in(self.0, alloc) }
+    }
     fn node(&self) -> &RBTreeNode<K, T> {
         unsafe { &*self.0 }
     }
@@ -138,6 +157,87 @@ impl<K: Ord, T> NodePtr<K, T> {
     pub fn swap_color(&self, other: &Self) {
         std::mem::swap(&mut self.node_mut().color, &mut other.node_mut().color);
     }
+    /// amount of nodes in the subtree, 0 for null.
+    pub fn size(self) -> usize {
+        if self.is_null() { 0 } else { self.node().size }
+    }
+    /// recomputes the subtree size from the children.
+    fn update_size(self) {
+        self.node_mut().size = self.left().size() + self.right().size() + 1;
+    }
+    /// adds `delta` to the size of this node and every ancestor.
+    fn add_size_to_ancestors(self, delta: isize) {
+        let mut current = self;
+        while !current.is_null() {
+            current.node_mut().size = current.node().size.wrapping_add_signed(delta);
+            current = current.parent();
+        }
+    }
+    /// position of the node in the order of its whole tree, walking up to the root.
+    pub fn rank(self) -> usize {
+        let mut rank = self.left().size();
+        let mut current = self;
+        while !current.parent().is_null() {
+            if current.is_right_child() {
+                rank += current.parent().left().size() + 1;
+            }
+            current = current.parent();
+        }
+        rank
+    }
+    fn root(self) -> NodePtr<K, T> {
+        let mut current = self;
+        while !current.parent().is_null() {
+            current = current.parent();
+        }
+        current
+    }
+    /// node at position `n` of the subtree, null if it's out of bounds.
+    fn select(self, mut n: usize) -> NodePtr<K, T> {
+        let mut current = self;
+        while !current.is_null() {
+            let left = current.left().size();
+            match n.cmp(&left) {
+                Ordering::Less => current = current.left(),
+                Ordering::Equal => return current,
+                Ordering::Greater => {
+                    n -= left + 1;
+                    current = current.right();
+                }
+            }
+        }
+        current
+    }
+    /// next node in order, null if this is the last one.
+    pub fn successor(self) -> NodePtr<K, T> {
+        let mut current = self.right();
+        if !current.is_null() {
+            while !current.left().is_null() {
+                current = current.left();
+            }
+            return current;
+        }
+        current = self;
+        while !current.parent().is_null() && current.is_right_child() {
+            current = current.parent();
+        }
+        current.parent()
+    }
+    /// previous node in order, null if this is the first one.
+    pub fn predecessor(self) -> NodePtr<K, T> {
+        let mut current = self.left();
+        if !current.is_null() {
+            while !current.right().is_null() {
+                current = current.right();
+            }
+            return current;
+        }
+        current = self;
+        while !current.parent().is_null() && current.is_left_child() {
+            current = current.parent();
+        }
+        current.parent()
+    }
 }
 
 pub struct RBTree<K: Ord, T, A: Allocator = Global> {
@@ -155,33 +255,98 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         Self { root: NodePtr::null(), len: 0, alloc }
     }
     pub fn insert(&mut self, key: K, value: T) {
-        let mut node = NodePtr::new(key, value);
+        let (parent, left) = self.leaf_position(&key);
+        self.insert_at(parent, left, key, value);
+    }
+    /// Like `insert`, but hands the entry back instead of aborting when the allocator fails.
+    pub fn try_insert(&mut self, key: K, value: T) -> Result<(), CollectionError<(K, T)>> {
+        let (parent, left) = self.leaf_position(&key);
+        let node = NodePtr::try_new_in(key, value, &self.alloc).map_err(CollectionError::CapacityFull)?;
+        self.link(parent, left, node);
+        Ok(())
+    }
+    /// Parent a new node for `key` goes under, after any equal keys, and whether it goes on the left.
+    fn leaf_position(&self, key: &K) -> (NodePtr<K, T>, bool) {
         let mut parent = NodePtr::null();
+        let mut left = false;
         let mut current = self.root;
         while !current.is_null() {
             parent = current;
-            if node.key() < current.key() {
+            left = key < current.key();
+            if left {
                 current = current.left();
             } else {
                 current = current.right();
             }
         }
+        (parent, left)
+    }
+    /// Walks down to `key`, returning its node or the parent a node for it goes under
+    /// and whether it goes on the left.
+    pub(crate) fn search_insert_position(&self, key: &K) -> Result<NodePtr<K, T>, (NodePtr<K, T>, bool)> {
+        let mut parent = NodePtr::null();
+        let mut left = false;
+        let mut current = self.root;
+        while !current.is_null() {
+            parent = current;
+            left = match key.cmp(current.key()) {
+                Ordering::Less => true,
+                Ordering::Greater => false,
+                Ordering::Equal => return Ok(current),
+            };
+            current = if left { current.left() } else { current.right() };
+        }
+        Err((parent, left))
+    }
+    /// Links a new node under `parent` without searching for its position again.
+    pub(crate) fn insert_at(&mut self, parent: NodePtr<K, T>, left: bool, key: K, value: T) -> NodePtr<K, T> {
+        let node = NodePtr::new_in(key, value, &self.alloc);
+        self.link(parent, left, node);
+        node
+    }
+    fn link(&mut self, parent: NodePtr<K, T>, left: bool, node: NodePtr<K, T>) {
         node.set_parent(parent);
         if parent.is_null() {
             self.root = node;
-        } else if node.key() < parent.key() {
+        } else if left {
             parent.set_left_child(node);
         } else {
             parent.set_right_child(node);
         }
+        parent.add_size_to_ancestors(1);
         self.len += 1;
         self.fix_insert(node);
     }
+    /// Amount of keys smaller than `key`, which is the position `key` has or would have in order.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut rank = 0;
+        let mut current = self.root;
+        while !current.is_null() {
+            if key <= current.key().borrow() {
+                current = current.left();
+            } else {
+                rank += current.left().size() + 1;
+                current = current.right();
+            }
+        }
+        rank
+    }
+    /// Entry at position `n` in order, counting from 0.
+    pub fn select(&self, n: usize) -> Option<(&K, &T)> {
+        let node = self.root.select(n);
+        if node.is_null() {
+            None
+        } else {
+            unsafe { Some((&(*node.0).key, &(*node.0).value)) }
+        }
+    }
     #[inline]
     pub fn len(&self) -> usize {
         self.len
     }
-    pub fn get(&self, key: &K) -> Option<&T> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         let mut node = self.find_node(key);
         if node.is_null() {
             None
@@ -189,15 +354,17 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
             unsafe { Some(std::mem::transmute(node.value())) }
         }
     }
-    pub fn get_mut(&self, key: &K) -> Option<&mut T> {
-        let mut node = self.find_node(&key);
+    pub fn get_mut<Q>(&self, key: &Q) -> Option<&mut T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut node = self.find_node(key);
         if node.is_null() {
             None
         } else {
             unsafe { Some(std::mem::transmute(node.value_mut())) }
         }
     }
-    pub fn get_key_value(&self, key: &K) -> Option<(&K, &T)> {
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &T)>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         let mut node = self.find_node(key);
         if node.is_null() {
             None
@@ -209,19 +376,20 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         self.len == 0
     }
     #[inline]
-    pub(crate) fn find_node(&self, k: &K) -> NodePtr<K, T> {
-        self.find_node_by(|p|p.cmp(k))
+    pub(crate) fn find_node<Q>(&self, k: &Q) -> NodePtr<K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.find_node_by(|p: &Q| p.cmp(k))
     }
     #[inline]
-    pub(crate) fn find_node_by<F>(&self, mut f: F) -> NodePtr<K, T> 
-        where F: FnMut(&K) -> Ordering {
+    pub(crate) fn find_node_by<Q, F>(&self, mut f: F) -> NodePtr<K, T> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q) -> Ordering {
         if self.root.is_null() {
             return NodePtr::null();
         }
         let mut temp = &self.root;
         unsafe {
             loop {
-                let next = match f(&(*temp.0).key) {
+                let next = match f((*temp.0).key.borrow()) {
                     Ordering::Greater => &mut (*temp.0).left,
                     Ordering::Less => &mut (*temp.0).right,
                     Ordering::Equal => return *temp,
@@ -235,15 +403,15 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         NodePtr::null()
     }
     #[inline]
-    fn find_node_with_values_by<F>(&self, mut f: F) -> NodePtr<K, T> 
-        where F: FnMut(&K, &T) -> Ordering {
+    fn find_node_with_values_by<Q, F>(&self, mut f: F) -> NodePtr<K, T> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q, &T) -> Ordering {
         if self.root.is_null() {
             return NodePtr::null();
         }
         let mut temp = &self.root;
         unsafe {
             loop {
-                let next = match f(&(*temp.0).key, &(*temp.0).value) {
+                let next = match f((*temp.0).key.borrow(), &(*temp.0).value) {
                     Ordering::Greater => &mut (*temp.0).left,
                     Ordering::Less => &mut (*temp.0).right,
                     Ordering::Equal => return *temp,
@@ -257,9 +425,14 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         NodePtr::null()
     }
     fn delete(&mut self, z: NodePtr<K, T>) -> Option<T> {
+        self.delete_entry(z).map(|(_, value)| value)
+    }
+    /// unlinks and frees `z`, returning its key and value. Other nodes stay where they are.
+    fn delete_entry(&mut self, z: NodePtr<K, T>) -> Option<(K, T)> {
         let mut x = NodePtr::<K, T>::null();
         let mut y = NodePtr::<K, T>::null();
-        
+        // x can be null, so its parent is tracked separately
+        let mut x_parent = NodePtr::<K, T>::null();
         if z.is_null() {
             return None;
         }
@@ -267,19 +440,23 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         let mut y_original_color = y.color();
         if z.left().is_null() {
             x = z.right();
+            x_parent = z.parent();
             self.transplant(z, z.right());
         } else if z.right().is_null() {
             x = z.left();
+            x_parent = z.parent();
             self.transplant(z, z.left());
         } else {
             y = Self::minimum_node(z.right());
             y_original_color = y.color();
             x = y.right();
             if y.parent() == z {
+                x_parent = y;
                 if !x.is_null() {
                     x.set_parent(y);
                 }
             } else {
+                x_parent = y.parent();
                 self.transplant(y, y.right());
                 y.set_right_child(z.right());
                 y.right().set_parent(y);
@@ -288,52 +465,58 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
             y.set_left_child(z.left());
             y.left().set_parent(y);
             y.set_color(z.color());
+            y.node_mut().size = z.node().size;
         }
-        let from_z = unsafe { Box::from_raw(z.0) };
-        let val = unsafe { Some(std::ptr::read(&from_z.value)) };
+        // every node from where x ended up to the root lost one node below it
+        x_parent.add_size_to_ancestors(-1);
         if y_original_color == Color::Black {
-            self.fix_remove(x);
+            self.fix_remove(x, x_parent);
         }
-        val
+        self.len -= 1;
+        let node = unsafe { z.into_box(&self.alloc) };
+        Some((node.key, node.value))
     }
-    pub fn remove_by<F>(&mut self, f: F) -> Option<T> 
-        where F: FnMut(&K) -> Ordering{
+    pub fn remove_by<Q, F>(&mut self, f: F) -> Option<T> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q) -> Ordering{
         let z = self.find_node_by(f);
         self.delete(z)
     }
-    pub fn remove_wth_values_by<F>(&mut self, f: F) -> Option<T> 
-        where F: FnMut(&K, &T) -> Ordering{
+    pub fn remove_wth_values_by<Q, F>(&mut self, f: F) -> Option<T> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q, &T) -> Ordering{
         let z = self.find_node_with_values_by(f);
         self.delete(z)
     }
-    pub fn search_and_remove_by<F>(&mut self, f: F) -> Result<T, Option<T>> 
-        where F: FnMut(&K) -> Ordering{
+    pub fn search_and_remove_by<Q, F>(&mut self, f: F) -> Result<T, Option<T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q) -> Ordering{
         match self.search_node_by(f) {
             Ok(o) => Ok(self.delete(o).unwrap()),
             Err(e) => Err(self.delete(e)),
         }
     }
-    pub fn search_and_remove_with_values_by<F>(&mut self, f: F) -> Result<T, Option<T>> 
-        where F: FnMut(&K, &T) -> Ordering{
+    pub fn search_and_remove_with_values_by<Q, F>(&mut self, f: F) -> Result<T, Option<T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q, &T) -> Ordering{
         match self.search_node_with_values_by(f) {
             Ok(o) => Ok(self.delete(o).unwrap()),
             Err(e) => Err(self.delete(e)),
         }
     }
-    pub fn search_and_remove(&mut self, k: &K) -> Result<T, Option<T>> {
-        self.search_and_remove_by(|p|p.cmp(k))
+    pub fn search_and_remove<Q>(&mut self, k: &Q) -> Result<T, Option<T>>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.search_and_remove_by(|p: &Q| p.cmp(k))
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<T> {
-        self.remove_by(|p|p.cmp(key))
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.remove_by(|p: &Q| p.cmp(key))
     }
     /// Searches for the value inside a given key. If a key isn't found, it finds the next
     /// highest key and returns the value. If there is no highest key returns the last key found.
-    pub fn search(&self, k: &K) -> Result<T, Option<T>> {
-        self.search_by(|p| p.cmp(k))
+    pub fn search<Q>(&self, k: &Q) -> Result<T, Option<T>>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.search_by(|p: &Q| p.cmp(k))
     }
-    pub fn search_by<F>(&self, mut f: F) -> Result<T, Option<T>> 
-        where F: FnMut(&K) -> Ordering {
+    pub fn search_by<Q, F>(&self, mut f: F) -> Result<T, Option<T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q) -> Ordering {
         self.search_node_by(f).map(|val|{
             unsafe { std::ptr::read(val.value()) }
         }).map_err(|val|{
@@ -345,8 +528,8 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         })
 
     }
-    pub fn search_node_by<F>(&self, mut f: F) -> Result<NodePtr<K, T>, NodePtr<K, T>> 
-        where F: FnMut(&K) -> Ordering {
+    pub fn search_node_by<Q, F>(&self, mut f: F) -> Result<NodePtr<K, T>, NodePtr<K, T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q) -> Ordering {
             if self.root.is_null() {
                 return Err(NodePtr::null());
             }
@@ -354,7 +537,7 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
             let mut larger_than_value = NodePtr::<K, T>::null();
             unsafe {
                 loop {
-                    let next = match f(&(*temp.0).key) {
+                    let next = match f((*temp.0).key.borrow()) {
                         Ordering::Greater => { larger_than_value = *temp; &mut (*temp.0).left },
                         Ordering::Less => &mut (*temp.0).right,
                         Ordering::Equal => return Ok(*temp),
@@ -366,8 +549,8 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
                 }
             }
     }
-    pub fn search_node_with_values_by<F>(&self, mut f: F) -> Result<NodePtr<K, T>, NodePtr<K, T>> 
-        where F: FnMut(&K, &T) -> Ordering {
+    pub fn search_node_with_values_by<Q, F>(&self, mut f: F) -> Result<NodePtr<K, T>, NodePtr<K, T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q, &T) -> Ordering {
             if self.root.is_null() {
                 return Err(NodePtr::null());
             }
@@ -375,7 +558,7 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
             let mut larger_than_value = NodePtr::<K, T>::null();
             unsafe {
                 loop {
-                    let next = match f(&(*temp.0).key, &(*temp.0).value) {
+                    let next = match f((*temp.0).key.borrow(), &(*temp.0).value) {
                         Ordering::Greater => { larger_than_value = *temp; &mut (*temp.0).left },
                         Ordering::Less => &mut (*temp.0).right,
                         Ordering::Equal => return Ok(*temp),
@@ -387,8 +570,8 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
                 }
             }
     }
-    pub fn search_with_values_by<F>(&self, mut f: F) -> Result<T, Option<T>> 
-        where F: FnMut(&K, &T) -> Ordering {
+    pub fn search_with_values_by<Q, F>(&self, mut f: F) -> Result<T, Option<T>> 
+        where K: Borrow<Q>, Q: ?Sized, F: FnMut(&Q, &T) -> Ordering {
         self.search_node_with_values_by(f).map(|val|{
             unsafe { std::ptr::read(val.value()) }
         }).map_err(|val|{
@@ -402,6 +585,68 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
     pub fn iter(&self) -> Iter<K, T, A> {
         Iter::new(self)
     }
+    /// Iterates over the keys in `range` in order, from either end.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let (front, back) = self.range_nodes(range);
+        Range { front, back, marker_: PhantomData }
+    }
+    pub fn range_mut<Q, R>(&mut self, range: R) -> RangeMut<'_, K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let (front, back) = self.range_nodes(range);
+        RangeMut { range: Range { front, back, marker_: PhantomData }, marker_: PhantomData }
+    }
+    /// first and last node in `range`, both null if it's empty.
+    fn range_nodes<Q, R>(&self, range: R) -> (NodePtr<K, T>, NodePtr<K, T>)
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        let front = self.lower_bound_node(range.start_bound());
+        let back = self.upper_bound_node(range.end_bound());
+        if front.is_null() || back.is_null() || front.key() > back.key() {
+            (NodePtr::null(), NodePtr::null())
+        } else {
+            (front, back)
+        }
+    }
+    /// first node whose key is inside `bound`.
+    fn lower_bound_node<Q>(&self, bound: Bound<&Q>) -> NodePtr<K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut current = self.root;
+        let mut found = NodePtr::null();
+        while !current.is_null() {
+            let inside = match bound {
+                Bound::Included(start) => current.key().borrow() >= start,
+                Bound::Excluded(start) => current.key().borrow() > start,
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = current;
+                current = current.left();
+            } else {
+                current = current.right();
+            }
+        }
+        found
+    }
+    /// last node whose key is inside `bound`.
+    fn upper_bound_node<Q>(&self, bound: Bound<&Q>) -> NodePtr<K, T>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        let mut current = self.root;
+        let mut found = NodePtr::null();
+        while !current.is_null() {
+            let inside = match bound {
+                Bound::Included(end) => current.key().borrow() <= end,
+                Bound::Excluded(end) => current.key().borrow() < end,
+                Bound::Unbounded => true,
+            };
+            if inside {
+                found = current;
+                current = current.right();
+            } else {
+                current = current.left();
+            }
+        }
+        found
+    }
     pub fn iter_mut(&mut self) -> IterMut<K, T, A> {
         IterMut::new(self)
     }
@@ -453,6 +698,8 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         }
         y.set_left_child(x);
         x.set_parent(y);
+        y.node_mut().size = x.node().size;
+        x.update_size();
     }
     fn right_rotate(&mut self, x: NodePtr<K, T>) {
         let y = x.left();
@@ -470,6 +717,8 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         }
         y.set_right_child(x);
         x.set_parent(y);
+        y.node_mut().size = x.node().size;
+        x.update_size();
     }
     fn transplant(&mut self, u: NodePtr<K, T>, v: NodePtr<K, T>) {
         if u.parent().is_null() {
@@ -530,67 +779,63 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         }
         self.root.set_color(Color::Black);
     }
-    fn fix_remove(&mut self, mut node: NodePtr<K, T>) {
-        while node != self.root && node.is_black_node() {
-            if node.is_left_child() {
-                let mut sibling = node.parent().right();
+    /// restores the colors after removing a black node, `node` took its place under `parent`.
+    /// `node` may be null, null nodes count as black.
+    fn fix_remove(&mut self, mut node: NodePtr<K, T>, mut parent: NodePtr<K, T>) {
+        while node != self.root && !node.is_red_node() {
+            if node == parent.left() {
+                let mut sibling = parent.right();
                 if sibling.is_red_node() {
                     sibling.set_color(Color::Black);
-                    node.parent().set_color(Color::Red);
-                    self.left_rotate(node.parent());
-                    sibling = node.parent().right();
+                    parent.set_color(Color::Red);
+                    self.left_rotate(parent);
+                    sibling = parent.right();
                 }
-                if sibling.left().is_black_node() && sibling.right().is_black_node() {
+                if !sibling.left().is_red_node() && !sibling.right().is_red_node() {
                     sibling.set_color(Color::Red);
-                    node = node.parent();
+                    node = parent;
+                    parent = node.parent();
                 } else {
-                    if sibling.right().is_black_node() {
-                        if !sibling.left().is_null() {
-                            sibling.left().set_color(Color::Black);
-                        }
+                    if !sibling.right().is_red_node() {
+                        sibling.left().set_color(Color::Black);
                         sibling.set_color(Color::Red);
                         self.right_rotate(sibling);
-                        sibling = node.parent().right();
-                    }
-                    sibling.set_color(node.parent().color());
-                    node.parent().set_color(Color::Black);
-                    if !sibling.right().is_null() {
-                        sibling.right().set_color(Color::Black);
+                        sibling = parent.right();
                     }
-                    self.left_rotate(node.parent());
+                    sibling.set_color(parent.color());
+                    parent.set_color(Color::Black);
+                    sibling.right().set_color(Color::Black);
+                    self.left_rotate(parent);
                     node = self.root;
                 }
             } else {
-                let mut sibling = node.parent().left();
+                let mut sibling = parent.left();
                 if sibling.is_red_node() {
                     sibling.set_color(Color::Black);
-                    node.parent().set_color(Color::Red);
-                    self.right_rotate(node.parent());
-                    sibling = node.parent().left();
+                    parent.set_color(Color::Red);
+                    self.right_rotate(parent);
+                    sibling = parent.left();
                 }
-                if sibling.left().is_black_node() && sibling.right().is_black_node() {
+                if !sibling.left().is_red_node() && !sibling.right().is_red_node() {
                     sibling.set_color(Color::Red);
-                    node = node.parent();
+                    node = parent;
+                    parent = node.parent();
                 } else {
-                    if sibling.left().is_black_node() {
-                        if !sibling.right().is_null() {
-                            sibling.right().set_color(Color::Black);
-                        }
+                    if !sibling.left().is_red_node() {
+                        sibling.right().set_color(Color::Black);
                         sibling.set_color(Color::Red);
                         self.left_rotate(sibling);
-                        sibling = node.parent().left();
+                        sibling = parent.left();
                     }
-                    sibling.set_color(node.parent().color());
-                    node.parent().set_color(Color::Black);
-                    if !sibling.left().is_null() {
-                        sibling.left().set_color(Color::Black);
-                    }
-                    self.right_rotate(node.parent());
+                    sibling.set_color(parent.color());
+                    parent.set_color(Color::Black);
+                    sibling.left().set_color(Color::Black);
+                    self.right_rotate(parent);
                     node = self.root;
                 }
             }
         }
-        self.root.set_color(Color::Black);
+        node.set_color(Color::Black);
     }
     fn print_helper(&self, root: NodePtr<K, T>, indent: &mut String, last: bool, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result
         where K: Display {
@@ -618,20 +863,36 @@ impl<K: Ord, T, A: Allocator> RBTree<K, T, A> {
         Ok(())
     }
     pub fn clear(&mut self) {
-        let mut iter = self.iter();
-        while let Some(node) = iter.next_inner() {
-            let from_node = unsafe { Box::from_raw(node.0) };
-        }
+        self.free_nodes();
+        self.root = NodePtr::null();
         self.len = 0;
     }
+    /// drops and frees every node, leaving the tree dangling.
+    fn free_nodes(&mut self) {
+        // frees children before their parent, so walking back up never reads a freed node
+        let mut current = self.root;
+        while !current.is_null() {
+            if !current.left().is_null() {
+                current = current.left();
+            } else if !current.right().is_null() {
+                current = current.right();
+            } else {
+                let parent = current.parent();
+                if !parent.is_null() && current.is_left_child() {
+                    parent.set_left_child(NodePtr::null());
+                } else if !parent.is_null() {
+                    parent.set_right_child(NodePtr::null());
+                }
+                drop(unsafe { current.into_box(&self.alloc) });
+                current = parent;
+            }
+        }
+    }
 }
 
 impl<K: Ord, T, A: Allocator> Drop for RBTree<K, T, A> {
     fn drop(&mut self) {
-        let mut iter = self.iter();
-        while let Some(node) = iter.next_inner() {
-            let from_node = unsafe { Box::from_raw(node.0) };
-        }
+        self.free_nodes();
     }
 }
 
@@ -642,90 +903,148 @@ impl<K: Ord + Display, T> Display for RBTree<K, T> {
     }
 }
 
+/// Iterator over the entries of a [`RBTree`] in order. It walks the parent links of the nodes
+/// from both ends, so it doesn't allocate.
 pub struct Iter<'a, K: Ord, T, A: Allocator> {
-    root: &'a RBTree<K, T, A>,
-    stack: Vec<NodePtr<K, T>>,
+    front: NodePtr<K, T>,
+    back: NodePtr<K, T>,
+    /// entries left between `front` and `back`.
+    len: usize,
+    marker_: PhantomData<&'a RBTree<K, T, A>>,
 }
 
 impl<'a, K: Ord, T, A: Allocator> Iter<'a, K, T, A> {
     pub fn new(tree: &'a RBTree<K, T, A>) -> Self {
-        let mut stack = Vec::with_capacity(tree.len);
-        if !tree.root.is_null() {
-            stack.push(tree.root);
-            let mut current = tree.root.left();
-            while !current.is_null() {
-                stack.push(current);
-                current = current.left();
-            }
-        }
-        Self { root: tree, stack }
+        let (front, back) = if tree.root.is_null() {
+            (NodePtr::null(), NodePtr::null())
+        } else {
+            (RBTree::<K, T, A>::minimum_node(tree.root), RBTree::<K, T, A>::maximum_node(tree.root))
+        };
+        Self { front, back, len: tree.len, marker_: PhantomData }
     }
     pub fn next_inner(&mut self) -> Option<NodePtr<K, T>> {
-        if let Some(node) = self.stack.pop() {
-            if !node.right().is_null() {
-                self.stack.push(node.right());
-                let mut current = node.right().left();
-                while !current.is_null() {
-                    self.stack.push(current);
-                    current = current.left();
-                }
-            }
-            Some(node)
-        } else {
-            None
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.front;
+        self.len -= 1;
+        if self.len > 0 {
+            self.front = node.successor();
+        }
+        Some(node)
+    }
+    /// skips `n` nodes from the front in O(log n) using the subtree sizes.
+    fn skip_front(&mut self, n: usize) {
+        if n >= self.len {
+            self.len = 0;
+        } else if n > 0 {
+            self.front = self.front.root().select(self.front.rank() + n);
+            self.len -= n;
+        }
+    }
+    /// skips `n` nodes from the back in O(log n) using the subtree sizes.
+    fn skip_back(&mut self, n: usize) {
+        if n >= self.len {
+            self.len = 0;
+        } else if n > 0 {
+            self.back = self.back.root().select(self.back.rank() - n);
+            self.len -= n;
         }
     }
+    pub fn next_back_inner(&mut self) -> Option<NodePtr<K, T>> {
+        if self.len == 0 {
+            return None;
+        }
+        let node = self.back;
+        self.len -= 1;
+        if self.len > 0 {
+            self.back = node.predecessor();
+        }
+        Some(node)
+    }
+}
+
+impl<K: Ord, T, A: Allocator> Clone for Iter<'_, K, T, A> {
+    fn clone(&self) -> Self {
+        Self { front: self.front, back: self.back, len: self.len, marker_: PhantomData }
+    }
 }
 
 impl<'a, K: Ord, T, A: Allocator> Iterator for Iter<'a, K, T, A> {
     type Item = (&'a K, &'a T);
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe { Some(std::mem::transmute(self.next_inner()?.node().pair_ref())) }
+        let node = self.next_inner()?;
+        unsafe { Some((&(*node.0).key, &(*node.0).value)) }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.skip_front(n);
+        self.next()
+    }
+}
+
+impl<K: Ord, T, A: Allocator> DoubleEndedIterator for Iter<'_, K, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.next_back_inner()?;
+        unsafe { Some((&(*node.0).key, &(*node.0).value)) }
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.skip_back(n);
+        self.next_back()
     }
 }
 
+impl<K: Ord, T, A: Allocator> ExactSizeIterator for Iter<'_, K, T, A> {}
+impl<K: Ord, T, A: Allocator> FusedIterator for Iter<'_, K, T, A> {}
+
 pub struct IterMut<'a, K: Ord, T, A: Allocator> {
-    root: &'a mut RBTree<K, T, A>,
-    stack: Vec<NodePtr<K, T>>,
+    iter: Iter<'a, K, T, A>,
+    marker_: PhantomData<&'a mut RBTree<K, T, A>>,
 }
 
 impl<'a, K: Ord, T, A: Allocator> IterMut<'a, K, T, A> {
     pub fn new(tree: &'a mut RBTree<K, T, A>) -> Self {
-        let mut stack = Vec::with_capacity(tree.len);
-        if !tree.root.is_null() {
-            stack.push(tree.root);
-            let mut current = tree.root.left();
-            while !current.is_null() {
-                stack.push(current);
-                current = current.left();
-            }
-        }
-        Self { root: tree, stack }
+        Self { iter: Iter::new(tree), marker_: PhantomData }
     }
     pub fn next_inner(&mut self) -> Option<NodePtr<K, T>> {
-        if let Some(node) = self.stack.pop() {
-            if !node.right().is_null() {
-                self.stack.push(node.right());
-                let mut current = node.right().left();
-                while !current.is_null() {
-                    self.stack.push(current);
-                    current = current.left();
-                }
-            }
-            Some(node)
-        } else {
-            None
-        }
+        self.iter.next_inner()
+    }
+    pub fn next_back_inner(&mut self) -> Option<NodePtr<K, T>> {
+        self.iter.next_back_inner()
     }
 }
 
 impl<'a, K: Ord, T, A: Allocator> Iterator for IterMut<'a, K, T, A> {
     type Item = (&'a K, &'a mut T);
     fn next(&mut self) -> Option<Self::Item> {
-        unsafe { Some(std::mem::transmute(self.next_inner()?.node().pair_ref())) }
+        let node = self.next_inner()?;
+        unsafe { Some((&(*node.0).key, &mut (*node.0).value)) }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.skip_front(n);
+        self.next()
+    }
+}
+
+impl<K: Ord, T, A: Allocator> DoubleEndedIterator for IterMut<'_, K, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.next_back_inner()?;
+        unsafe { Some((&(*node.0).key, &mut (*node.0).value)) }
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.skip_back(n);
+        self.next_back()
     }
 }
 
+impl<K: Ord, T, A: Allocator> ExactSizeIterator for IterMut<'_, K, T, A> {}
+impl<K: Ord, T, A: Allocator> FusedIterator for IterMut<'_, K, T, A> {}
+
 pub struct Values<'a, K: Ord, T, A: Allocator> {
     iter: Iter<'a, K, T, A>,
 }
@@ -735,7 +1054,26 @@ impl<'a, K: Ord, T, A: Allocator> Iterator for Values<'a, K, T, A> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|(a, b)|b)
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n).map(|(a, b)|b)
+    }
+}
+
+impl<K: Ord, T, A: Allocator> DoubleEndedIterator for Values<'_, K, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(a, b)|b)
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth_back(n).map(|(a, b)|b)
+    }
 }
+
+impl<K: Ord, T, A: Allocator> ExactSizeIterator for Values<'_, K, T, A> {}
+impl<K: Ord, T, A: Allocator> FusedIterator for Values<'_, K, T, A> {}
+
 pub struct Keys<'a, K: Ord, T, A: Allocator> {
     iter: Iter<'a, K, T, A>,
 }
@@ -745,8 +1083,26 @@ impl<'a, K: Ord, T, A: Allocator> Iterator for Keys<'a, K, T, A> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|(a, b)|a)
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n).map(|(a, b)|a)
+    }
+}
+
+impl<K: Ord, T, A: Allocator> DoubleEndedIterator for Keys<'_, K, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(a, b)|a)
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth_back(n).map(|(a, b)|a)
+    }
 }
 
+impl<K: Ord, T, A: Allocator> ExactSizeIterator for Keys<'_, K, T, A> {}
+impl<K: Ord, T, A: Allocator> FusedIterator for Keys<'_, K, T, A> {}
+
 pub struct ValuesMut<'a, K: Ord, T, A: Allocator> {
     iter: IterMut<'a, K, T, A>,
 }
@@ -756,4 +1112,259 @@ impl<'a, K: Ord, T, A: Allocator> Iterator for ValuesMut<'a, K, T, A> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next().map(|(a, b)|b)
     }
-}
\ No newline at end of file
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n).map(|(a, b)|b)
+    }
+}
+
+impl<K: Ord, T, A: Allocator> DoubleEndedIterator for ValuesMut<'_, K, T, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(a, b)|b)
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth_back(n).map(|(a, b)|b)
+    }
+}
+
+impl<K: Ord, T, A: Allocator> ExactSizeIterator for ValuesMut<'_, K, T, A> {}
+impl<K: Ord, T, A: Allocator> FusedIterator for ValuesMut<'_, K, T, A> {}
+
+/// Iterator over the entries of a key range, see [`RBTree::range`].
+pub struct Range<'a, K: Ord, T> {
+    front: NodePtr<K, T>,
+    back: NodePtr<K, T>,
+    marker_: PhantomData<(&'a K, &'a T)>,
+}
+
+impl<K: Ord, T> Range<'_, K, T> {
+    fn next_node(&mut self) -> Option<NodePtr<K, T>> {
+        let node = self.front;
+        if node.is_null() {
+            return None;
+        }
+        if node == self.back {
+            self.front = NodePtr::null();
+            self.back = NodePtr::null();
+        } else {
+            self.front = node.successor();
+        }
+        Some(node)
+    }
+    fn next_back_node(&mut self) -> Option<NodePtr<K, T>> {
+        let node = self.back;
+        if node.is_null() {
+            return None;
+        }
+        if node == self.front {
+            self.front = NodePtr::null();
+            self.back = NodePtr::null();
+        } else {
+            self.back = node.predecessor();
+        }
+        Some(node)
+    }
+}
+
+impl<K: Ord, T> FusedIterator for Range<'_, K, T> {}
+
+impl<'a, K: Ord, T> Iterator for Range<'a, K, T> {
+    type Item = (&'a K, &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.next_node()?;
+        unsafe { Some((&(*node.0).key, &(*node.0).value)) }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.front.is_null() {
+            (0, Some(0))
+        } else {
+            (1, None)
+        }
+    }
+}
+
+impl<K: Ord, T> DoubleEndedIterator for Range<'_, K, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.next_back_node()?;
+        unsafe { Some((&(*node.0).key, &(*node.0).value)) }
+    }
+}
+
+/// Mutable iterator over the entries of a key range, see [`RBTree::range_mut`].
+pub struct RangeMut<'a, K: Ord, T> {
+    range: Range<'a, K, T>,
+    marker_: PhantomData<&'a mut T>,
+}
+
+impl<'a, K: Ord, T> Iterator for RangeMut<'a, K, T> {
+    type Item = (&'a K, &'a mut T);
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.range.next_node()?;
+        unsafe { Some((&(*node.0).key, &mut (*node.0).value)) }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<K: Ord, T> FusedIterator for RangeMut<'_, K, T> {}
+
+impl<K: Ord, T> DoubleEndedIterator for RangeMut<'_, K, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let node = self.range.next_back_node()?;
+        unsafe { Some((&(*node.0).key, &mut (*node.0).value)) }
+    }
+}
+#[cfg(test)]
+mod test {
+    use std::{alloc::{AllocError, Allocator, Global, Layout}, cell::{Cell, RefCell}, collections::HashSet, ptr::NonNull, rc::Rc};
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{NodePtr, RBTree, RBTreeMap, RBTreeSet};
+
+    /// Checks the red-black rules, the parent links, the key order and the subtree sizes of every node.
+    pub(super) fn assert_valid<K: Ord, T, A: Allocator>(tree: &RBTree<K, T, A>) {
+        assert!(!tree.root.is_red_node() && (tree.root.is_null() || tree.root.parent().is_null()), "Testing the root is black");
+        assert!(tree.root.size() == tree.len, "Testing the root's size is the length");
+        check_subtree(tree.root, NodePtr::null());
+    }
+    /// black height of the subtree.
+    fn check_subtree<K: Ord, T>(node: NodePtr<K, T>, parent: NodePtr<K, T>) -> usize {
+        if node.is_null() {
+            return 1;
+        }
+        let (left, right) = (node.left(), node.right());
+        assert!(node.parent() == parent, "Testing parent links");
+        assert!(!node.is_red_node() || (!left.is_red_node() && !right.is_red_node()), "Testing red nodes have black children");
+        assert!((left.is_null() || left.key() < node.key()) && (right.is_null() || right.key() > node.key()), "Testing key order");
+        assert!(node.size() == left.size() + right.size() + 1, "Testing subtree sizes");
+        let height = check_subtree(left, node);
+        assert!(height == check_subtree(right, node), "Testing both sides have the same black height");
+        height + node.is_black_node() as usize
+    }
+
+    /// allocator recording the address of every live allocation, to catch leaks and double frees.
+    #[derive(Default)]
+    struct CountingAllocator {
+        live: RefCell<HashSet<usize>>,
+        allocations: Cell<usize>,
+    }
+
+    unsafe impl Allocator for CountingAllocator {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            let ptr = Global.allocate(layout)?;
+            assert!(self.live.borrow_mut().insert(ptr.cast::<u8>().as_ptr() as usize), "Testing addresses aren't handed out twice");
+            self.allocations.set(self.allocations.get() + 1);
+            Ok(ptr)
+        }
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            assert!(self.live.borrow_mut().remove(&(ptr.as_ptr() as usize)), "Testing nodes are freed exactly once");
+            unsafe { Global.deallocate(ptr, layout) }
+        }
+    }
+
+    #[test]
+    fn allocator_test() {
+        let allocator = CountingAllocator::default();
+        let value = Rc::new(());
+        let mut map = RBTreeMap::new_in(&allocator);
+        for key in 0..100 {
+            map.insert(key, value.clone());
+        }
+        map.insert(50, value.clone());
+        assert!(allocator.allocations.get() == 100 && allocator.live.borrow().len() == 100, "Testing every node is allocated through the tree's allocator");
+        for key in (0..100).step_by(3) {
+            map.remove(&key);
+        }
+        assert!(allocator.live.borrow().len() == 66 && Rc::strong_count(&value) == 67, "Testing remove frees the node and drops the value");
+        map.clear();
+        assert!(allocator.live.borrow().is_empty() && Rc::strong_count(&value) == 1, "Testing clear frees every node");
+        for key in 0..50 {
+            map.insert(key, value.clone());
+        }
+        drop(map);
+        assert!(allocator.live.borrow().is_empty() && Rc::strong_count(&value) == 1, "Testing drop frees every node");
+        assert!(allocator.allocations.get() == 150, "Testing nodes aren't allocated more than needed");
+    }
+    #[test]
+    fn iter_test() {
+        for len in 0..20usize {
+            let mut map = RBTreeMap::new();
+            for key in (0..len).rev() {
+                map.insert(key, key*2);
+            }
+            let mut iter = map.iter();
+            let (mut front, mut back) = (0, len);
+            while front < back {
+                let (item, key) = if (front + back).is_multiple_of(2) {
+                    front += 1;
+                    (iter.next(), front - 1)
+                } else {
+                    back -= 1;
+                    (iter.next_back(), back)
+                };
+                assert!(item == Some((&key, &(key*2))), "Testing alternating ends");
+                assert!(iter.len() == back - front, "Testing len after each step");
+            }
+            for _ in 0..3 {
+                assert!(iter.next().is_none() && iter.next_back().is_none() && iter.len() == 0, "Testing exhausted iterators stay empty");
+            }
+            let mut values = map.values_mut();
+            while let (Some(first), last) = (values.next(), values.next_back()) {
+                *first += 1;
+                if let Some(last) = last {
+                    *last += 1;
+                }
+            }
+            assert!(values.next().is_none() && values.next_back().is_none(), "Testing exhausted mutable iterators stay empty");
+            assert!(map.iter().all(|(&key, &value)| value == key*2 + 1), "Testing both ends of values_mut reach every value once");
+            let mut keys = map.keys();
+            assert!(keys.len() == len && keys.next_back() == len.checked_sub(1).as_ref() && keys.len() == len.saturating_sub(1), "Testing keys from the back");
+            assert!(map.iter_mut().rev().map(|(&key, _)| key).eq((0..len).rev()), "Testing iter_mut from the back");
+        }
+    }
+    #[test]
+    fn order_statistics_test() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut tree = RBTree::new();
+        let mut set = RBTreeSet::new();
+        let mut reference: Vec<i32> = Vec::new();
+        for _ in 0..1000 {
+            let key = rng.random_range(0..200);
+            match reference.binary_search(&key) {
+                Ok(index) if rng.random_bool(0.5) => {
+                    reference.remove(index);
+                    assert!(tree.remove(&key).is_some() && set.remove(&key), "Testing remove");
+                }
+                Ok(_) => {}
+                Err(index) => {
+                    reference.insert(index, key);
+                    tree.insert(key, ());
+                    set.insert(key);
+                }
+            }
+            assert_valid(&tree);
+            let probe = rng.random_range(-1..201);
+            assert!(tree.rank(&probe) == reference.partition_point(|&key| key < probe) && set.rank(&probe) == tree.rank(&probe), "Testing rank of {probe}");
+            let index = rng.random_range(0..=reference.len());
+            assert!(tree.select(index).map(|(key, _)| key) == reference.get(index) && set.get_index(index) == reference.get(index), "Testing get_index({index})");
+            assert!(tree.iter().nth(index).map(|(key, _)| key) == reference.get(index), "Testing nth");
+            assert!(tree.iter().nth_back(index).map(|(key, _)| key) == reference.iter().nth_back(index), "Testing nth_back");
+            assert!(set.iter().nth(index) == reference.get(index) && set.iter().nth_back(index) == reference.iter().nth_back(index), "Testing nth and nth_back on sets");
+        }
+        for (index, key) in reference.iter().enumerate() {
+            assert!(set.rank(key) == index && set.get_index(index) == Some(key), "Testing rank and get_index invert each other");
+        }
+        let mut iter = tree.iter();
+        let mut expected = reference.iter();
+        while iter.len() > 0 {
+            let (skip, skip_back) = (rng.random_range(0..4), rng.random_range(0..4));
+            assert!(iter.nth(skip).map(|(key, _)| key) == expected.nth(skip), "Testing nth after skipping from both ends");
+            assert!(iter.nth_back(skip_back).map(|(key, _)| key) == expected.nth_back(skip_back), "Testing nth_back after skipping from both ends");
+            assert!(iter.len() == expected.len(), "Testing len after skipping");
+        }
+    }
+}
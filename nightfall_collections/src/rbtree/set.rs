@@ -1,4 +1,4 @@
-use std::{alloc::{Allocator, Global}, iter::Chain};
+use std::{alloc::{Allocator, Global}, borrow::Borrow, iter::{Chain, FusedIterator}, ops::RangeBounds};
 
 use super::{RBTree, RBTreeMap};
 
@@ -17,15 +17,22 @@ impl<K: Ord, A: Allocator> RBTreeSet<K, A> {
     pub fn insert(&mut self, key: K) -> bool {
         self.base.insert(key, ()).is_some()
     }
-    pub fn get(&self, key: &K) -> Option<&K> {
+    pub fn get<Q>(&self, key: &Q) -> Option<&K>
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.get_key_value(key).map(|(k,t)|k)
     }
-    pub fn remove(&mut self, key: &K) -> bool {
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.base.remove(key).is_some()
     }
     pub fn iter(&self) -> Iter<'_, K, A> {
         Iter { iter: self.base.keys() }
     }
+    /// Iterates over the keys in `range`, in order and from either end.
+    pub fn range<Q, R>(&self, range: R) -> Range<'_, K>
+        where K: Borrow<Q>, Q: Ord + ?Sized, R: RangeBounds<Q> {
+        Range { iter: self.base.range(range) }
+    }
     pub fn difference<'a>(&'a self, other: &'a RBTreeSet<K, A>) -> Difference<'a, K, A> {
         Difference { iter: self.iter(), other }
     }
@@ -59,13 +66,23 @@ impl<K: Ord, A: Allocator> RBTreeSet<K, A> {
     pub fn is_clear(&self) -> bool {
         self.base.is_clear()
     }
-    pub fn contains(&self, key: &K) -> bool {
+    pub fn contains<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>, Q: Ord + ?Sized {
         self.get(key).is_some()
     }
     #[inline]
     pub fn len(&self) -> usize {
         self.base.len()
     }
+    /// Amount of keys smaller than `key`.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+        where K: Borrow<Q>, Q: Ord + ?Sized {
+        self.base.rank(key)
+    }
+    /// Key at position `index` in order, in O(log n).
+    pub fn get_index(&self, index: usize) -> Option<&K> {
+        self.base.get_index(index).map(|(key, _)| key)
+    }
 }
 impl<K: Ord, A: Allocator> Extend<K> for RBTreeSet<K, A> {
     fn extend<T: IntoIterator<Item = K>>(&mut self, iter: T) {
@@ -93,6 +110,46 @@ impl<'a, K: Ord, A: Allocator> Iterator for Iter<'a, K, A> {
     fn next(&mut self) -> Option<Self::Item> {
         self.iter.next()
     }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n)
+    }
+}
+
+impl<K: Ord, A: Allocator> DoubleEndedIterator for Iter<'_, K, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth_back(n)
+    }
+}
+
+impl<K: Ord, A: Allocator> ExactSizeIterator for Iter<'_, K, A> {}
+impl<K: Ord, A: Allocator> FusedIterator for Iter<'_, K, A> {}
+
+pub struct Range<'a, K: Ord> {
+    iter: super::Range<'a, K, ()>,
+}
+
+impl<'a, K: Ord> Iterator for Range<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, _)| key)
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K: Ord> FusedIterator for Range<'_, K> {}
+
+impl<K: Ord> DoubleEndedIterator for Range<'_, K> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(key, _)| key)
+    }
 }
 
 pub struct Difference<'a, K: Ord, A: Allocator> {
@@ -197,4 +254,25 @@ impl<'a, K: Ord + 'a, A: Allocator + 'a> Iterator for SymmetricDifference<'a, K,
     {
         self.iter.fold(init, f)
     }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::BTreeSet, ops::Bound};
+
+    use super::RBTreeSet;
+    #[test]
+    fn range_test() {
+        let set = RBTreeSet::from([1, 3, 5, 7, 9]);
+        let reference = BTreeSet::from([1, 3, 5, 7, 9]);
+        for bounds in [(Bound::Included(3), Bound::Excluded(9)), (Bound::Excluded(1), Bound::Unbounded), (Bound::Unbounded, Bound::Included(4))] {
+            assert!(set.range(bounds).eq(reference.range(bounds)) && set.range(bounds).rev().eq(reference.range(bounds).rev()), "Testing range {bounds:?}");
+        }
+        let mut range = set.range(2..6);
+        assert!(range.size_hint().0 == 1, "Testing non empty ranges hint at an entry");
+        range.next();
+        range.next_back();
+        assert!(range.size_hint() == (0, Some(0)) && range.next().is_none(), "Testing exhausted ranges hint at nothing");
+        assert!(set.range(4..5).size_hint() == (0, Some(0)), "Testing empty ranges hint at nothing");
+    }
 }
\ No newline at end of file
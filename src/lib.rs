@@ -1,4 +1,5 @@
 #![feature(allocator_api)]
 pub use nightfall_allocators as alloc;
 pub use nightfall_collections as collections;
-pub mod typemap;
\ No newline at end of file
+pub mod typemap;
+pub mod replay;
\ No newline at end of file
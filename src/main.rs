@@ -1,5 +1,116 @@
-use nightfall_allocators::arena::StandardArena;
-use nightfall_collections::{bitmap::Bitmap, CircularBuffer};
+use std::{fs::File, io::BufReader, process::ExitCode};
 
-fn main() {
+use nightfall::replay::{region_size, replay_named, ReplayReport, ALLOCATORS};
+use nightfall_allocators::trace::read_trace;
+
+const USAGE: &str = "usage: nightfall replay <trace> [--allocator <name|all>] [--region <bytes>]
+
+Replays an allocation trace, as written by RecordingAllocator, against the crate's allocators.
+allocators: ptr, standard, pool, tlsf, freelist-first, freelist-best";
+
+struct ReplayArgs {
+    trace: String,
+    allocator: String,
+    region: Option<usize>,
+}
+
+fn parse_args(args: &[String]) -> Result<ReplayArgs, String> {
+    let mut args = args.iter();
+    match args.next().map(String::as_str) {
+        Some("replay") => {}
+        Some(command) => return Err(format!("unknown command `{command}`")),
+        None => return Err("missing command".to_string()),
+    }
+    let mut trace = None;
+    let mut allocator = "all".to_string();
+    let mut region = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--allocator" => allocator = args.next().ok_or("missing value for --allocator")?.clone(),
+            "--region" => {
+                let value = args.next().ok_or("missing value for --region")?;
+                region = Some(value.parse().map_err(|_| format!("invalid region size `{value}`"))?);
+            }
+            _ if trace.is_none() => trace = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument `{arg}`")),
+        }
+    }
+    if allocator != "all" && !ALLOCATORS.contains(&allocator.as_str()) {
+        return Err(format!("unknown allocator `{allocator}`"));
+    }
+    Ok(ReplayArgs { trace: trace.ok_or("missing trace file")?, allocator, region })
+}
+
+fn print_report(report: &ReplayReport) {
+    println!(
+        "{:<16}{:>10}{:>10}{:>16.0}{:>14}{:>16}{:>14.1}%",
+        report.allocator,
+        report.events,
+        report.failures,
+        report.throughput(),
+        report.peak_live,
+        report.peak_footprint,
+        report.fragmentation()*100.0,
+    );
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args = match parse_args(&args) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("error: {error}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let events = match File::open(&args.trace).map_err(Into::into).and_then(|file| read_trace(BufReader::new(file))) {
+        Ok(events) => events,
+        Err(error) => {
+            eprintln!("error: couldn't read `{}`: {error}", args.trace);
+            return ExitCode::FAILURE;
+        }
+    };
+    let region = args.region.unwrap_or_else(|| region_size(&events));
+    println!("{:<16}{:>10}{:>10}{:>16}{:>14}{:>16}{:>15}", "allocator", "events", "failures", "events/s", "peak live", "peak footprint", "fragmentation");
+    let allocators: Vec<&str> = if args.allocator == "all" {
+        ALLOCATORS.to_vec()
+    } else {
+        vec![args.allocator.as_str()]
+    };
+    for allocator in allocators {
+        match replay_named(allocator, &events, region) {
+            Some(report) => print_report(&report),
+            None => eprintln!("error: couldn't allocate a region of {region} bytes for `{allocator}`"),
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse_args;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+    #[test]
+    fn parse_args_test() {
+        let parsed = parse_args(&args(&["replay", "trace.txt"])).unwrap();
+        assert!(parsed.trace == "trace.txt" && parsed.allocator == "all" && parsed.region.is_none(), "Testing the defaults");
+        let parsed = parse_args(&args(&["replay", "--allocator", "tlsf", "trace.txt", "--region", "4096"])).unwrap();
+        assert!(parsed.trace == "trace.txt" && parsed.allocator == "tlsf" && parsed.region == Some(4096), "Testing options in any order");
+        for (bad, error) in [
+            (&[][..], "missing command"),
+            (&["record"][..], "unknown command `record`"),
+            (&["replay"][..], "missing trace file"),
+            (&["replay", "a", "b"][..], "unexpected argument `b`"),
+            (&["replay", "a", "--allocator"][..], "missing value for --allocator"),
+            (&["replay", "a", "--allocator", "malloc"][..], "unknown allocator `malloc`"),
+            (&["replay", "a", "--region", "big"][..], "invalid region size `big`"),
+            (&["replay", "a", "--region", "-1"][..], "invalid region size `-1`"),
+        ] {
+            let result = parse_args(&args(bad));
+            assert!(result.as_ref().err().map(String::as_str) == Some(error), "Testing {bad:?} fails with `{error}`");
+        }
+    }
 }
\ No newline at end of file
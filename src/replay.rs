@@ -0,0 +1,272 @@
+use std::{alloc::{Allocator, Layout}, collections::HashMap, ptr::NonNull, time::{Duration, Instant}};
+
+use nightfall_allocators::{arena::{Arena, PtrArena, StandardArena}, heap::{FitStrategy, FreeListAllocator, TlsfAllocator}, pool::{Pool, PoolAllocator}, trace::TraceEvent};
+
+/// Allocators a trace can be replayed against.
+pub const ALLOCATORS: [&str; 6] = ["ptr", "standard", "pool", "tlsf", "freelist-first", "freelist-best"];
+
+/// Result of replaying a trace against one allocator.
+#[derive(Debug, Clone)]
+pub struct ReplayReport {
+    pub allocator: &'static str,
+    pub events: usize,
+    /// events the allocator couldn't satisfy.
+    pub failures: usize,
+    pub elapsed: Duration,
+    /// highest amount of bytes the trace had live at once.
+    pub peak_live: usize,
+    /// highest amount of memory the allocator needed at once to hold those bytes.
+    pub peak_footprint: usize,
+}
+
+impl ReplayReport {
+    /// events per second.
+    pub fn throughput(&self) -> f64 {
+        self.events as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+    /// share of the peak footprint that wasn't live data, from padding, headers or unusable holes.
+    pub fn fragmentation(&self) -> f64 {
+        if self.peak_footprint == 0 {
+            0.0
+        } else {
+            1.0 - (self.peak_live as f64 / self.peak_footprint as f64).min(1.0)
+        }
+    }
+}
+
+/// How the footprint of an allocator is measured while replaying.
+enum Footprint<'a> {
+    /// highest address handed out, relative to the start of the region.
+    Region(usize),
+    /// measured by the allocator itself.
+    Measured(&'a dyn Fn() -> usize),
+}
+
+/// Memory region the region based allocators manage.
+struct Region {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl Region {
+    /// `None` if `size` bytes can't be allocated.
+    fn new(size: usize) -> Option<Self> {
+        let layout = Layout::from_size_align(size.max(1), 4096).ok()?;
+        let ptr = NonNull::new(unsafe { std::alloc::alloc(layout) })?;
+        Some(Self { ptr, layout })
+    }
+    fn address(&self) -> usize {
+        self.ptr.as_ptr() as usize
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+/// Slot type used to replay traces against a [`Pool`].
+#[repr(C, align(16))]
+struct Slot<const N: usize>([u8; N]);
+
+/// Lets a [`Pool`] take part in a replay, allocations that don't fit in a slot fail.
+struct PoolReplay<T>(Pool<T>);
+
+unsafe impl<T> Allocator for PoolReplay<T> {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, std::alloc::AllocError> {
+        if layout.size() > std::mem::size_of::<T>() || layout.align() > std::mem::align_of::<T>() {
+            return Err(std::alloc::AllocError);
+        }
+        let ptr = self.0.allocate().map_err(|_|std::alloc::AllocError)?;
+        Ok(NonNull::slice_from_raw_parts(ptr.cast(), std::mem::size_of::<T>()))
+    }
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, _: Layout) {
+        let _ = self.0.deallocate(ptr.cast());
+    }
+}
+
+/// Memory needed to replay `events` against an allocator that never frees, saturating at `usize::MAX`.
+pub fn region_size(events: &[TraceEvent]) -> usize {
+    let total = events.iter().fold(0usize, |total, event| total.saturating_add(match event {
+        TraceEvent::Alloc { size, align, .. } => size.saturating_add(*align),
+        TraceEvent::Realloc { size, .. } => size.saturating_add(16),
+        TraceEvent::Free { .. } => 0,
+    }));
+    total.max(1024*1024)
+}
+
+/// Replays `events` against the allocator called `name`, one of [`ALLOCATORS`]. Region based
+/// allocators get `region_size` bytes to work with. Returns `None` for unknown names or when
+/// the region can't be allocated.
+pub fn replay_named(name: &str, events: &[TraceEvent], region_size: usize) -> Option<ReplayReport> {
+    let region = Region::new(region_size)?;
+    let base = region.address();
+    let report = match name {
+        "ptr" => {
+            let arena = unsafe { PtrArena::from_raw(region.ptr.as_ptr(), region_size) };
+            replay("ptr", &arena, events, Footprint::Region(base))
+        }
+        "standard" => {
+            let arena = StandardArena::new(4096);
+            replay("standard", &arena, events, Footprint::Measured(&|| arena.size()))
+        }
+        "pool" => {
+            let largest = events.iter().map(|event| match event {
+                TraceEvent::Alloc { size, .. } | TraceEvent::Realloc { size, .. } => *size,
+                TraceEvent::Free { .. } => 0,
+            }).max().unwrap_or(0);
+            // pick the smallest slot that fits every allocation of the trace
+            match largest {
+                0..=16 => replay_pool::<Slot<16>>(&region, events),
+                17..=64 => replay_pool::<Slot<64>>(&region, events),
+                65..=256 => replay_pool::<Slot<256>>(&region, events),
+                257..=1024 => replay_pool::<Slot<1024>>(&region, events),
+                _ => replay_pool::<Slot<4096>>(&region, events),
+            }
+        }
+        "tlsf" => {
+            let tlsf = unsafe { TlsfAllocator::from_raw(region.ptr.as_ptr(), region_size) };
+            replay("tlsf", &tlsf, events, Footprint::Region(base))
+        }
+        "freelist-first" | "freelist-best" => {
+            let (name, strategy) = if name == "freelist-first" {
+                ("freelist-first", FitStrategy::FirstFit)
+            } else {
+                ("freelist-best", FitStrategy::BestFit)
+            };
+            let heap = unsafe { FreeListAllocator::from_raw(region.ptr.as_ptr(), region_size, strategy) };
+            replay(name, &heap, events, Footprint::Region(base))
+        }
+        _ => return None,
+    };
+    Some(report)
+}
+
+fn replay_pool<T>(region: &Region, events: &[TraceEvent]) -> ReplayReport {
+    let size = region.layout.size() - region.layout.size() % std::mem::size_of::<T>();
+    let pool = PoolReplay(unsafe { Pool::<T>::from_raw(region.ptr.as_ptr(), size) });
+    replay("pool", &pool, events, Footprint::Region(region.address()))
+}
+
+fn replay<A: Allocator>(name: &'static str, allocator: &A, events: &[TraceEvent], footprint: Footprint) -> ReplayReport {
+    let mut live: HashMap<u64, (NonNull<u8>, Layout)> = HashMap::new();
+    let mut failures = 0;
+    let mut live_bytes = 0;
+    let mut peak_live = 0;
+    let mut high_water = 0;
+    let mut peak_footprint = 0;
+    let start = Instant::now();
+    for event in events {
+        let allocation = match *event {
+            // an id that's still live would lose track of its allocation
+            TraceEvent::Alloc { id, .. } if live.contains_key(&id) => None,
+            TraceEvent::Alloc { id, size, align } => {
+                // layouts no allocator can satisfy count as failed events
+                Layout::from_size_align(size, align).ok()
+                    .and_then(|layout| allocator.allocate(layout).ok().map(|ptr| (id, ptr, layout)))
+            }
+            TraceEvent::Realloc { id, size } => {
+                let Some((ptr, old)) = live.remove(&id) else {
+                    failures += 1;
+                    continue;
+                };
+                live_bytes -= old.size();
+                let result = Layout::from_size_align(size, old.align()).map_err(|_| std::alloc::AllocError).and_then(|new| {
+                    let ptr = if size >= old.size() {
+                        unsafe { allocator.grow(ptr, old, new) }
+                    } else {
+                        unsafe { allocator.shrink(ptr, old, new) }
+                    };
+                    ptr.map(|ptr| (ptr, new))
+                });
+                match result {
+                    Ok((ptr, new)) => Some((id, ptr, new)),
+                    Err(_) => {
+                        // the old allocation is still valid when reallocating fails
+                        unsafe { allocator.deallocate(ptr, old) };
+                        None
+                    }
+                }
+            }
+            TraceEvent::Free { id } => {
+                match live.remove(&id) {
+                    Some((ptr, layout)) => {
+                        unsafe { allocator.deallocate(ptr, layout) };
+                        live_bytes -= layout.size();
+                    }
+                    None => failures += 1,
+                }
+                continue;
+            }
+        };
+        let Some((id, ptr, layout)) = allocation else {
+            failures += 1;
+            continue;
+        };
+        live.insert(id, (ptr.cast(), layout));
+        live_bytes += layout.size();
+        peak_live = peak_live.max(live_bytes);
+        match footprint {
+            Footprint::Region(base) => {
+                high_water = high_water.max(ptr.as_ptr().cast::<u8>() as usize + ptr.len() - base);
+                peak_footprint = high_water;
+            }
+            Footprint::Measured(measure) => peak_footprint = peak_footprint.max(measure()),
+        }
+    }
+    let elapsed = start.elapsed();
+    for (_, (ptr, layout)) in live {
+        unsafe { allocator.deallocate(ptr, layout) };
+    }
+    ReplayReport { allocator: name, events: events.len(), failures, elapsed, peak_live, peak_footprint }
+}
+
+#[cfg(test)]
+mod test {
+    use nightfall_allocators::trace::TraceEvent;
+
+    use super::{region_size, replay_named};
+
+    const REGION: usize = 1024*1024;
+
+    fn alloc(id: u64, size: usize, align: usize) -> TraceEvent {
+        TraceEvent::Alloc { id, size, align }
+    }
+    #[test]
+    fn accounting_test() {
+        let events = [alloc(0, 64, 8), alloc(1, 128, 16), TraceEvent::Realloc { id: 0, size: 256 }, TraceEvent::Free { id: 1 }, TraceEvent::Free { id: 0 }];
+        for name in ["tlsf", "standard", "freelist-first"] {
+            let report = replay_named(name, &events, REGION).unwrap();
+            assert!(report.events == 5 && report.failures == 0, "Testing every event of {name} succeeded: {report:?}");
+            assert!(report.peak_live == 128 + 256, "Testing the peak counts the reallocated size: {report:?}");
+            assert!(report.peak_footprint >= report.peak_live, "Testing the footprint holds the live bytes: {report:?}");
+        }
+        assert!(replay_named("unknown", &events, REGION).is_none(), "Testing unknown allocators");
+    }
+    #[test]
+    fn unknown_id_test() {
+        let events = [TraceEvent::Free { id: 9 }, TraceEvent::Realloc { id: 9, size: 16 }, alloc(0, 16, 8), alloc(0, 32, 8)];
+        let report = replay_named("tlsf", &events, REGION).unwrap();
+        assert!(report.failures == 3, "Testing unknown and reused ids fail: {report:?}");
+        assert!(report.peak_live == 16, "Testing a reused id doesn't replace the live allocation: {report:?}");
+    }
+    #[test]
+    fn failed_realloc_test() {
+        // the arena only has room for the first allocation
+        let events = [alloc(0, 100, 8), TraceEvent::Realloc { id: 0, size: 300 }, TraceEvent::Free { id: 0 }];
+        let report = replay_named("ptr", &events, 256).unwrap();
+        assert!(report.failures == 2, "Testing a failed realloc drops the allocation: {report:?}");
+        assert!(report.peak_live == 100, "Testing the failed realloc isn't counted as live: {report:?}");
+    }
+    #[test]
+    fn oversized_test() {
+        let events = [alloc(0, usize::MAX, 8), alloc(1, isize::MAX as usize, 4096), alloc(2, 16, 8), TraceEvent::Realloc { id: 2, size: usize::MAX }];
+        assert!(region_size(&events) == usize::MAX, "Testing the region size saturates");
+        assert!(replay_named("tlsf", &events, region_size(&events)).is_none(), "Testing regions that can't be allocated");
+        for name in ["ptr", "standard", "pool", "tlsf"] {
+            let report = replay_named(name, &events, REGION).unwrap();
+            assert!(report.failures == 3 && report.peak_live == 16, "Testing oversized layouts fail on {name}: {report:?}");
+        }
+    }
+}